@@ -7,9 +7,16 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, contracterror, token, Address, Bytes, BytesN, Env, String,
+    contract, contractimpl, contracttype, contracterror, token, xdr::ToXdr, Address, Bytes, BytesN, Env, String,
 };
 
+/// Minimum balance of an account without subentries: two base reserves of 0.5 XLM, in stroops.
+/// Contracts can't read an account's reserve, so the native SAC still enforces the exact one.
+const MIN_ACCOUNT_BALANCE: i128 = 10_000_000;
+
+/// XDR of `Asset::Native`, from which the native SAC address is derived
+const NATIVE_ASSET_XDR: [u8; 4] = [0; 4];
+
 /// Immutable escrow parameters (set once at deployment, stored in instance storage)
 /// This mirrors the EVM Immutables struct exactly
 #[derive(Clone, Debug)]
@@ -23,7 +30,7 @@ pub struct Immutables {
     pub maker: Address,
     /// Address of the resolver/taker (packed as full address)
     pub taker: Address,
-    /// Token contract address (Address::zero() for native XLM)
+    /// Token contract address (the native SAC for native XLM)
     pub token: Address,
     /// Amount of tokens locked
     pub amount: i128,
@@ -161,6 +168,8 @@ pub enum Error {
     TokenTransferFailed = 10,
     /// Insufficient balance for escrow creation
     InsufficientBalance = 11,
+    /// Native XLM payment would leave an account below its minimum reserve, or pays an account that does not exist
+    NativeReserveNotMet = 12,
 }
 
 #[contractimpl] 
@@ -270,6 +279,9 @@ impl FusionPlusEscrow {
             return Err(Error::InvalidTime);
         }
 
+        // The maker may have spent since initialization, and must keep its reserve for native XLM
+        Self::verify_maker_balance(&env, &immutables.token, &immutables.maker, immutables.amount)?;
+
         // Transfer tokens from maker to contract
         // Use the same pattern as other contracts - all tokens use token::Client
         let token_client = token::Client::new(&env, &immutables.token);
@@ -560,15 +572,33 @@ impl FusionPlusEscrow {
         Ok(env.storage().instance().get(&DataKey::Cancelled).unwrap_or(false))
     }
 
+    /// Address of the native XLM SAC
+    fn native_token(env: &Env) -> Address {
+        env.deployer().with_stellar_asset(Bytes::from_array(env, &NATIVE_ASSET_XDR)).deployed_address()
+    }
+
     fn is_native_token(env: &Env, token: &Address) -> bool {
-        // In Soroban, native XLM is represented by the string "native"
-        // This is the standard way to identify native XLM in Soroban
-        let native_address = Address::from_string(&String::from_str(env, "native"));
-        *token == native_address
+        *token == Self::native_token(env)
+    }
+
+    /// Whether `address` is a Stellar account (G...): its XDR is SCV_ADDRESS followed by SC_ADDRESS_TYPE_ACCOUNT (0)
+    fn is_account(env: &Env, address: &Address) -> bool {
+        address.to_xdr(env).get(7) == Some(0)
+    }
+
+    /// A native XLM payment can't create an account, so an account recipient must already exist
+    fn verify_native_recipient(env: &Env, to: &Address) -> Result<(), Error> {
+        if Self::is_account(env, to) && token::Client::new(env, &Self::native_token(env)).try_balance(to).is_err() {
+            return Err(Error::NativeReserveNotMet);
+        }
+        Ok(())
     }
 
     fn transfer_tokens(env: &Env, immutables: &Immutables, to: &Address) -> Result<(), Error> {
         // Use the same pattern as other contracts - all tokens (including native XLM) use token::Client
+        if Self::is_native_token(env, &immutables.token) {
+            Self::verify_native_recipient(env, to)?;
+        }
         let token_client = token::Client::new(env, &immutables.token);
         
         // Transfer tokens from escrow to recipient
@@ -579,9 +609,8 @@ impl FusionPlusEscrow {
     }
 
     fn transfer_native(env: &Env, to: &Address, amount: i128) -> Result<(), Error> {
-        // Use the same pattern as the Resolver - native XLM uses the "native" address
-        let native = Address::from_string(&String::from_str(env, "native"));
-        let token_client = token::Client::new(env, &native);
+        Self::verify_native_recipient(env, to)?;
+        let token_client = token::Client::new(env, &Self::native_token(env));
         
         // Transfer native XLM from escrow to recipient
         token_client.transfer(&env.current_contract_address(), to, &amount);
@@ -596,20 +625,13 @@ impl FusionPlusEscrow {
     }
 
     fn verify_maker_balance(env: &Env, token: &Address, maker: &Address, amount: i128) -> Result<(), Error> {
-        if Self::is_native_token(env, token) {
-            // For native XLM, check if maker has enough XLM
-            let native_client = token::Client::new(env, &Address::from_string(&String::from_str(env, "native")));
-            let maker_balance = native_client.balance(&maker);
-            if maker_balance < amount {
-                return Err(Error::InsufficientBalance);
-            }
-        } else {
-            // For custom tokens, check if maker has enough tokens
-            let token_client = token::Client::new(env, token);
-            let maker_balance = token_client.balance(&maker);
-            if maker_balance < amount {
-                return Err(Error::InsufficientBalance);
-            }
+        let maker_balance = token::Client::new(env, token).balance(maker);
+        if maker_balance < amount {
+            return Err(Error::InsufficientBalance);
+        }
+        // A maker account locking native XLM must keep its minimum reserve
+        if Self::is_native_token(env, token) && Self::is_account(env, maker) && maker_balance - amount < MIN_ACCOUNT_BALANCE {
+            return Err(Error::NativeReserveNotMet);
         }
         Ok(())
     }
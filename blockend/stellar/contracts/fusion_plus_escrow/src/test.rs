@@ -1,466 +1,394 @@
 #![cfg(test)]
+extern crate std;
 
 use super::*;
 use soroban_sdk::{
-    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation},
-    Address, Env, Symbol,
+    testutils::{Address as _, Events, Ledger},
+    vec,
+    xdr::{
+        AccountEntry, AccountEntryExt, AccountId, FromXdr, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey, LedgerKeyAccount,
+        PublicKey, SequenceNumber, Thresholds, Uint256,
+    },
+    Address, Env, IntoVal,
 };
 
-fn setup_escrow(env: &Env) -> (FusionPlusEscrow, Address, Address, Address, Address, BytesN<32>, BytesN<32>, i128, i128) {
-    let escrow = FusionPlusEscrow;
-    let maker = Address::generate(env);
-    let taker = Address::generate(env);
-    let token = Address::generate(env);
-    let order_hash = BytesN::from_array(env, &[1u8; 32]);
-    let hash_lock = BytesN::from_array(env, &[2u8; 32]);
-    let amount = 1000000;
-    let safety_deposit = 100000;
-
-    let timelocks = TimelockParams {
-        finality: 60,
-        src_withdrawal: 120,
-        src_public_withdrawal: 180,
-        src_cancellation: 240,
-        src_public_cancellation: 300,
-        dst_withdrawal: 360,
-        dst_public_withdrawal: 420,
-        dst_cancellation: 480,
-    };
-
-    let init_params = InitParams {
-        order_hash: order_hash.clone(),
-        hash_lock: hash_lock.clone(),
-        maker: maker.clone(),
-        taker: taker.clone(),
-        token: token.clone(),
-        amount,
-        safety_deposit,
-        timelocks,
-    };
+// Safety deposits are native XLM, so escrows run against a native SAC deployed in the test
+// env, with accounts injected into its ledger since the native SAC has no admin to mint with
+
+const XLM: i128 = 10_000_000;
+
+/// Env whose ledger holds a Stellar account for each key, funded with the given XLM balance in stroops
+fn env_with_accounts(accounts: &[([u8; 32], i128)]) -> Env {
+    let mut snapshot = Env::default().to_snapshot();
+    for (key, balance) in accounts {
+        let account_id = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(*key)));
+        let entry = LedgerEntry {
+            data: LedgerEntryData::Account(AccountEntry {
+                account_id: account_id.clone(),
+                balance: *balance as i64,
+                flags: 0,
+                home_domain: Default::default(),
+                inflation_dest: None,
+                num_sub_entries: 0,
+                seq_num: SequenceNumber(0),
+                thresholds: Thresholds([1; 4]),
+                signers: Default::default(),
+                ext: AccountEntryExt::V0,
+            }),
+            last_modified_ledger_seq: 0,
+            ext: LedgerEntryExt::V0,
+        };
+        let key = LedgerKey::Account(LedgerKeyAccount { account_id });
+        snapshot.ledger.ledger_entries.push((std::boxed::Box::new(key), (std::boxed::Box::new(entry), None)));
+    }
+    let env = Env::from_snapshot(snapshot);
+    env.mock_all_auths();
+    env
+}
 
-    escrow.initialize(env, init_params).unwrap();
+/// Account (G...) address of a raw ed25519 key
+fn account(env: &Env, key: [u8; 32]) -> Address {
+    let mut xdr = [0u8; 44];
+    xdr[3] = 18;
+    xdr[12..].copy_from_slice(&key);
+    Address::from_xdr(env, &Bytes::from_array(env, &xdr)).unwrap()
+}
 
-    (escrow, maker, taker, token, order_hash, hash_lock, amount, safety_deposit)
+fn native_sac(env: &Env) -> token::Client<'static> {
+    let address = env.deployer().with_stellar_asset(Bytes::from_array(env, &[0; 4])).deploy();
+    token::Client::new(env, &address)
 }
 
-#[test]
-fn test_initialize() {
-    let env = Env::default();
-    let escrow = FusionPlusEscrow;
-    let maker = Address::generate(&env);
-    let taker = Address::generate(&env);
-    let token = Address::generate(&env);
-    let order_hash = BytesN::from_array(&env, &[1u8; 32]);
-    let hash_lock = BytesN::from_array(&env, &[2u8; 32]);
-    let amount = 1000000;
-    let safety_deposit = 100000;
-
-    let timelocks = TimelockParams {
-        finality: 60,
-        src_withdrawal: 120,
-        src_public_withdrawal: 180,
-        src_cancellation: 240,
-        src_public_cancellation: 300,
-        dst_withdrawal: 360,
-        dst_public_withdrawal: 420,
-        dst_cancellation: 480,
-    };
-
-    let init_params = InitParams {
-        order_hash: order_hash.clone(),
-        hash_lock: hash_lock.clone(),
+fn escrow_params(env: &Env, maker: &Address, taker: &Address, token: &Address, amount: i128, secret: &BytesN<32>) -> InitParams {
+    InitParams {
+        order_hash: BytesN::from_array(env, &[1u8; 32]),
+        hash_lock: env.crypto().keccak256(&Bytes::from_array(env, &secret.to_array())),
         maker: maker.clone(),
         taker: taker.clone(),
         token: token.clone(),
         amount,
-        safety_deposit,
-        timelocks,
-    };
-
-    // Initialize escrow
-    escrow.initialize(&env, init_params).unwrap();
-
-    // Verify initialization
-    assert!(env.storage().instance().has(&DataKey::Immutables));
-    assert!(!escrow.is_withdrawn_status(&env).unwrap());
-    assert!(!escrow.is_cancelled_status(&env).unwrap());
-
-    // Verify immutable data
-    let immutables = escrow.get_immutables(&env).unwrap();
-    assert_eq!(immutables.order_hash, order_hash);
-    assert_eq!(immutables.hash_lock, hash_lock);
-    assert_eq!(immutables.maker, maker);
-    assert_eq!(immutables.taker, taker);
-    assert_eq!(immutables.token, token);
-    assert_eq!(immutables.amount, amount);
-    assert_eq!(immutables.safety_deposit, safety_deposit);
-
-    // Verify event was emitted
-    let events = env.events().all();
-    assert_eq!(events.len(), 1);
-    let event = &events[0];
-    assert_eq!(event.topics[0], Symbol::new(&env, "EscrowCreated"));
+        safety_deposit: XLM,
+        timelocks: TimelockParams {
+            finality: 60,
+            src_withdrawal: 120,
+            src_public_withdrawal: 180,
+            src_cancellation: 240,
+            src_public_cancellation: 300,
+            dst_withdrawal: 360,
+            dst_public_withdrawal: 420,
+            dst_cancellation: 480,
+        },
+    }
 }
 
-#[test]
-#[should_panic(expected = "AlreadyInitialized")]
-fn test_initialize_twice() {
-    let env = Env::default();
-    let (escrow, _, _, _, _, _, _, _) = setup_escrow(&env);
-
-    // Try to initialize again - should fail
-    let maker = Address::generate(&env);
-    let taker = Address::generate(&env);
-    let token = Address::generate(&env);
-    let order_hash = BytesN::from_array(&env, &[3u8; 32]);
-    let hash_lock = BytesN::from_array(&env, &[4u8; 32]);
-    let amount = 2000000;
-    let safety_deposit = 200000;
-
-    let timelocks = TimelockParams {
-        finality: 60,
-        src_withdrawal: 120,
-        src_public_withdrawal: 180,
-        src_cancellation: 240,
-        src_public_cancellation: 300,
-        dst_withdrawal: 360,
-        dst_public_withdrawal: 420,
-        dst_cancellation: 480,
-    };
-
-    let init_params = InitParams {
-        order_hash,
-        hash_lock,
-        maker,
-        taker,
-        token,
-        amount,
-        safety_deposit,
-        timelocks,
-    };
 
-    escrow.initialize(&env, init_params).unwrap();
+/// Escrow of an issued token, deposited by a contract maker, whose taker paid the safety
+/// deposit. Stages open at 60 (finality), 120, 180, 240 and 300 seconds.
+struct Setup {
+    env: Env,
+    escrow: FusionPlusEscrowClient<'static>,
+    maker: Address,
+    taker: Address,
+    token: token::Client<'static>,
+    xlm: token::Client<'static>,
+    secret: BytesN<32>,
 }
 
-#[test]
-#[should_panic(expected = "InvalidParams")]
-fn test_initialize_invalid_amount() {
-    let env = Env::default();
-    let escrow = FusionPlusEscrow;
-    let maker = Address::generate(&env);
-    let taker = Address::generate(&env);
-    let token = Address::generate(&env);
-    let order_hash = BytesN::from_array(&env, &[1u8; 32]);
-    let hash_lock = BytesN::from_array(&env, &[2u8; 32]);
-    let amount = 0; // Invalid amount
-    let safety_deposit = 100000;
-
-    let timelocks = TimelockParams {
-        finality: 60,
-        src_withdrawal: 120,
-        src_public_withdrawal: 180,
-        src_cancellation: 240,
-        src_public_cancellation: 300,
-        dst_withdrawal: 360,
-        dst_public_withdrawal: 420,
-        dst_cancellation: 480,
-    };
-
-    let init_params = InitParams {
-        order_hash,
-        hash_lock,
-        maker,
-        taker,
-        token,
-        amount,
-        safety_deposit,
-        timelocks,
-    };
+fn setup() -> Setup {
+    let env = env_with_accounts(&[([9; 32], 100 * XLM)]);
+    let (maker, taker) = (Address::generate(&env), account(&env, [9; 32]));
+    let xlm = native_sac(&env);
+    let token = token::Client::new(&env, &env.register_stellar_asset_contract(Address::generate(&env)));
+    token::StellarAssetClient::new(&env, &token.address).mint(&maker, &(10 * XLM));
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
+    let secret = BytesN::from_array(&env, &[3u8; 32]);
 
-    escrow.initialize(&env, init_params).unwrap();
+    escrow.initialize(&escrow_params(&env, &maker, &taker, &token.address, 10 * XLM, &secret));
+    escrow.deposit();
+    xlm.transfer(&taker, &escrow.address, &XLM);
+    Setup { env, escrow, maker, taker, token, xlm, secret }
 }
 
-#[test]
-#[should_panic(expected = "InvalidParams")]
-fn test_initialize_invalid_timelocks() {
-    let env = Env::default();
-    let escrow = FusionPlusEscrow;
-    let maker = Address::generate(&env);
-    let taker = Address::generate(&env);
-    let token = Address::generate(&env);
-    let order_hash = BytesN::from_array(&env, &[1u8; 32]);
-    let hash_lock = BytesN::from_array(&env, &[2u8; 32]);
-    let amount = 1000000;
-    let safety_deposit = 100000;
-
-    let timelocks = TimelockParams {
-        finality: 120,
-        src_withdrawal: 60, // Invalid: src_withdrawal <= finality
-        src_public_withdrawal: 180,
-        src_cancellation: 240,
-        src_public_cancellation: 300,
-        dst_withdrawal: 360,
-        dst_public_withdrawal: 420,
-        dst_cancellation: 480,
-    };
-
-    let init_params = InitParams {
-        order_hash,
-        hash_lock,
-        maker,
-        taker,
-        token,
-        amount,
-        safety_deposit,
-        timelocks,
-    };
+/// Events of the escrow with the given name
+fn escrow_events(s: &Setup, name: &str) -> u32 {
+    let topics = vec![&s.env, String::from_str(&s.env, name).into_val(&s.env)];
+    s.env.events().all().iter().filter(|(contract, event_topics, _)| *contract == s.escrow.address && *event_topics == topics).count() as u32
+}
 
-    escrow.initialize(&env, init_params).unwrap();
+fn set_time(s: &Setup, timestamp: u64) {
+    s.env.ledger().with_mut(|li| li.timestamp = timestamp);
 }
 
 #[test]
-fn test_deposit() {
-    let env = Env::default();
-    let (escrow, maker, _, _, _, _, _, _) = setup_escrow(&env);
+fn test_initialize() {
+    let env = env_with_accounts(&[]);
+    let (maker, taker) = (Address::generate(&env), Address::generate(&env));
+    let token = env.register_stellar_asset_contract(Address::generate(&env));
+    token::StellarAssetClient::new(&env, &token).mint(&maker, &XLM);
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
+    let secret = BytesN::from_array(&env, &[3u8; 32]);
+    let params = escrow_params(&env, &maker, &taker, &token, XLM, &secret);
 
-    // Mock auth for maker
-    env.mock_all_auths();
+    escrow.initialize(&params);
+    assert!(!escrow.is_withdrawn_status());
+    assert!(!escrow.is_cancelled_status());
+
+    let immutables = escrow.get_immutables();
+    assert_eq!(immutables.order_hash, params.order_hash);
+    assert_eq!(immutables.hash_lock, params.hash_lock);
+    assert_eq!((immutables.maker, immutables.taker, immutables.token), (maker, taker, token));
+    assert_eq!((immutables.amount, immutables.safety_deposit), (XLM, XLM));
+    assert_eq!(immutables.timelocks.deployed_at, 0);
 
-    // Deposit should succeed
-    escrow.deposit(&env).unwrap();
+    let events = env.events().all();
+    let (contract, topics, _) = events.last().unwrap();
+    assert_eq!(contract, escrow.address);
+    assert_eq!(topics, vec![&env, String::from_str(&env, "EscrowCreated").into_val(&env)]);
 }
 
 #[test]
-#[should_panic(expected = "Unauthorized")]
-fn test_deposit_unauthorized() {
-    let env = Env::default();
-    let (escrow, _, taker, _, _, _, _, _) = setup_escrow(&env);
-
-    // Mock auth for taker (not maker) - should fail
-    env.mock_auths(&[&taker]);
-
-    escrow.deposit(&env).unwrap();
+fn test_initialize_twice() {
+    let s = setup();
+    let params = escrow_params(&s.env, &s.maker, &s.taker, &s.token.address, XLM, &s.secret);
+    assert_eq!(s.escrow.try_initialize(&params), Err(Ok(Error::AlreadyInitialized)));
 }
 
 #[test]
-fn test_withdraw() {
-    let env = Env::default();
-    let (escrow, _, taker, _, _, hash_lock, _, _) = setup_escrow(&env);
-
-    // Mock auth for taker
-    env.mock_all_auths();
-
-    // Create a valid secret (hash of which equals hash_lock)
+fn test_initialize_invalid_params() {
+    let env = env_with_accounts(&[]);
+    let (maker, taker) = (Address::generate(&env), Address::generate(&env));
+    let token = env.register_stellar_asset_contract(Address::generate(&env));
+    token::StellarAssetClient::new(&env, &token).mint(&maker, &XLM);
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
     let secret = BytesN::from_array(&env, &[3u8; 32]);
-    
-    // Mock the keccak256 function to return the expected hash
-    env.mock_crypto().keccak256.return_value = hash_lock;
-
-    // Withdraw should succeed
-    escrow.withdraw(&env, &secret).unwrap();
 
-    // Verify escrow is withdrawn
-    assert!(escrow.is_withdrawn_status(&env).unwrap());
-    assert!(!escrow.is_cancelled_status(&env).unwrap());
+    let zero_amount = escrow_params(&env, &maker, &taker, &token, 0, &secret);
+    assert_eq!(escrow.try_initialize(&zero_amount), Err(Ok(Error::InvalidParams)));
+    let mut no_deposit = escrow_params(&env, &maker, &taker, &token, XLM, &secret);
+    no_deposit.safety_deposit = 0;
+    assert_eq!(escrow.try_initialize(&no_deposit), Err(Ok(Error::InvalidParams)));
+    // Withdrawal opening before finality
+    let mut timelocks = escrow_params(&env, &maker, &taker, &token, XLM, &secret);
+    timelocks.timelocks.finality = 120;
+    timelocks.timelocks.src_withdrawal = 60;
+    assert_eq!(escrow.try_initialize(&timelocks), Err(Ok(Error::InvalidParams)));
+    let too_much = escrow_params(&env, &maker, &taker, &token, XLM + 1, &secret);
+    assert_eq!(escrow.try_initialize(&too_much), Err(Ok(Error::InsufficientBalance)));
+}
 
-    // Verify event was emitted
-    let events = env.events().all();
-    assert_eq!(events.len(), 2); // EscrowCreated + Withdrawal
-    let event = &events[1];
-    assert_eq!(event.topics[0], Symbol::new(&env, "Withdrawal"));
+#[test]
+fn test_deposit() {
+    let s = setup();
+    assert_eq!(s.token.balance(&s.maker), 0);
+    assert_eq!(s.token.balance(&s.escrow.address), 10 * XLM);
+    assert_eq!(s.xlm.balance(&s.escrow.address), XLM);
 }
 
 #[test]
-#[should_panic(expected = "InvalidSecret")]
-fn test_withdraw_invalid_secret() {
-    let env = Env::default();
-    let (escrow, _, _, _, _, _, _, _) = setup_escrow(&env);
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_deposit_requires_maker() {
+    let env = env_with_accounts(&[]);
+    let (maker, taker) = (Address::generate(&env), Address::generate(&env));
+    let token = env.register_stellar_asset_contract(Address::generate(&env));
+    token::StellarAssetClient::new(&env, &token).mint(&maker, &XLM);
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
+    escrow.initialize(&escrow_params(&env, &maker, &taker, &token, XLM, &BytesN::from_array(&env, &[3u8; 32])));
+    env.set_auths(&[]);
+
+    env.as_contract(&escrow.address, || {
+        let _ = FusionPlusEscrow::deposit(env.clone());
+    });
+}
 
-    // Mock auth for taker
-    env.mock_all_auths();
+#[test]
+fn test_withdraw() {
+    let s = setup();
+    // Not before the withdrawal stage
+    set_time(&s, 119);
+    assert_eq!(s.escrow.try_withdraw(&s.secret), Err(Ok(Error::InvalidTime)));
+
+    set_time(&s, 130);
+    s.escrow.withdraw(&s.secret);
+    assert!(s.escrow.is_withdrawn_status());
+    assert!(!s.escrow.is_cancelled_status());
+    assert_eq!(s.token.balance(&s.maker), 10 * XLM);
+    assert_eq!(s.xlm.balance(&s.taker), 100 * XLM);
+    assert_eq!(escrow_events(&s, "Withdrawal"), 1);
+}
 
-    // Create an invalid secret
-    let secret = BytesN::from_array(&env, &[3u8; 32]);
-    
-    // Mock the keccak256 function to return a different hash
-    env.mock_crypto().keccak256.return_value = BytesN::from_array(&env, &[4u8; 32]);
+#[test]
+fn test_withdraw_invalid_secret() {
+    let s = setup();
+    set_time(&s, 130);
+    assert_eq!(s.escrow.try_withdraw(&BytesN::from_array(&s.env, &[4u8; 32])), Err(Ok(Error::InvalidSecret)));
+}
 
-    escrow.withdraw(&env, &secret).unwrap();
+#[test]
+fn test_hash_lock_is_keccak256() {
+    let s = setup();
+    let escrow = FusionPlusEscrowClient::new(&s.env, &s.env.register_contract(None, FusionPlusEscrow));
+    let mut params = escrow_params(&s.env, &s.maker, &s.taker, &s.token.address, XLM, &s.secret);
+    params.hash_lock = s.env.crypto().sha256(&Bytes::from_array(&s.env, &s.secret.to_array()));
+    token::StellarAssetClient::new(&s.env, &s.token.address).mint(&s.maker, &XLM);
+    escrow.initialize(&params);
+
+    set_time(&s, 130);
+    assert_eq!(escrow.try_withdraw(&s.secret), Err(Ok(Error::InvalidSecret)));
 }
 
 #[test]
-#[should_panic(expected = "AlreadyWithdrawn")]
 fn test_withdraw_twice() {
-    let env = Env::default();
-    let (escrow, _, _, _, _, hash_lock, _, _) = setup_escrow(&env);
-
-    // Mock auth for taker
-    env.mock_all_auths();
-
-    // Create a valid secret
-    let secret = BytesN::from_array(&env, &[3u8; 32]);
-    
-    // Mock the keccak256 function
-    env.mock_crypto().keccak256.return_value = hash_lock;
-
-    // First withdrawal should succeed
-    escrow.withdraw(&env, &secret).unwrap();
-
-    // Second withdrawal should fail
-    escrow.withdraw(&env, &secret).unwrap();
+    let s = setup();
+    set_time(&s, 130);
+    s.escrow.withdraw(&s.secret);
+    assert_eq!(s.escrow.try_withdraw(&s.secret), Err(Ok(Error::AlreadyWithdrawn)));
 }
 
 #[test]
 fn test_public_withdraw() {
-    let env = Env::default();
-    let (escrow, _, _, _, _, hash_lock, _, _) = setup_escrow(&env);
-    let caller = Address::generate(&env);
-
-    // Mock auth for caller
-    env.mock_all_auths();
-
-    // Create a valid secret
-    let secret = BytesN::from_array(&env, &[3u8; 32]);
-    
-    // Mock the keccak256 function
-    env.mock_crypto().keccak256.return_value = hash_lock;
-
-    // Public withdrawal should succeed
-    escrow.public_withdraw(&env, &secret, &caller).unwrap();
-
-    // Verify escrow is withdrawn
-    assert!(escrow.is_withdrawn_status(&env).unwrap());
-    assert!(!escrow.is_cancelled_status(&env).unwrap());
-
-    // Verify event was emitted
-    let events = env.events().all();
-    assert_eq!(events.len(), 2); // EscrowCreated + Withdrawal
-    let event = &events[1];
-    assert_eq!(event.topics[0], Symbol::new(&env, "Withdrawal"));
+    let s = setup();
+    let caller = Address::generate(&s.env);
+    // The taker's private window comes first
+    set_time(&s, 130);
+    assert_eq!(s.escrow.try_public_withdraw(&s.secret, &caller), Err(Ok(Error::InvalidTime)));
+
+    set_time(&s, 190);
+    assert_eq!(s.escrow.try_withdraw(&s.secret), Err(Ok(Error::InvalidTime)));
+    s.escrow.public_withdraw(&s.secret, &caller);
+    assert!(s.escrow.is_withdrawn_status());
+    assert_eq!(s.token.balance(&s.maker), 10 * XLM);
+    assert_eq!(s.xlm.balance(&caller), XLM);
+    assert_eq!(escrow_events(&s, "Withdrawal"), 1);
 }
 
 #[test]
 fn test_cancel() {
-    let env = Env::default();
-    let (escrow, maker, _, _, _, _, _, _) = setup_escrow(&env);
-
-    // Mock auth for maker
-    env.mock_all_auths();
-
-    // Cancel should succeed
-    escrow.cancel(&env, &maker).unwrap();
-
-    // Verify escrow is cancelled
-    assert!(!escrow.is_withdrawn_status(&env).unwrap());
-    assert!(escrow.is_cancelled_status(&env).unwrap());
-
-    // Verify event was emitted
-    let events = env.events().all();
-    assert_eq!(events.len(), 2); // EscrowCreated + EscrowCancelled
-    let event = &events[1];
-    assert_eq!(event.topics[0], Symbol::new(&env, "EscrowCancelled"));
+    let s = setup();
+    set_time(&s, 250);
+    // Only the taker cancels privately
+    assert_eq!(s.escrow.try_cancel(&s.maker), Err(Ok(Error::Unauthorized)));
+
+    s.escrow.cancel(&s.taker);
+    assert!(!s.escrow.is_withdrawn_status());
+    assert!(s.escrow.is_cancelled_status());
+    assert_eq!(s.token.balance(&s.maker), 10 * XLM);
+    assert_eq!(s.xlm.balance(&s.maker), XLM);
+    assert_eq!(escrow_events(&s, "EscrowCancelled"), 1);
 }
 
 #[test]
 fn test_public_cancel() {
-    let env = Env::default();
-    let (escrow, _, _, _, _, _, _, _) = setup_escrow(&env);
-    let caller = Address::generate(&env);
-
-    // Mock auth for caller
-    env.mock_all_auths();
-
-    // Public cancel should succeed
-    escrow.public_cancel(&env, &caller).unwrap();
-
-    // Verify escrow is cancelled
-    assert!(!escrow.is_withdrawn_status(&env).unwrap());
-    assert!(escrow.is_cancelled_status(&env).unwrap());
-
-    // Verify event was emitted
-    let events = env.events().all();
-    assert_eq!(events.len(), 2); // EscrowCreated + EscrowCancelled
-    let event = &events[1];
-    assert_eq!(event.topics[0], Symbol::new(&env, "EscrowCancelled"));
+    let s = setup();
+    let caller = Address::generate(&s.env);
+    set_time(&s, 250);
+    assert_eq!(s.escrow.try_public_cancel(&caller), Err(Ok(Error::InvalidTime)));
+
+    set_time(&s, 310);
+    s.escrow.public_cancel(&caller);
+    assert!(s.escrow.is_cancelled_status());
+    assert_eq!(s.token.balance(&s.maker), 10 * XLM);
+    assert_eq!(s.xlm.balance(&caller), XLM);
+    assert_eq!(escrow_events(&s, "EscrowCancelled"), 1);
 }
 
 #[test]
-#[should_panic(expected = "AlreadyCancelled")]
 fn test_cancel_twice() {
-    let env = Env::default();
-    let (escrow, maker, _, _, _, _, _, _) = setup_escrow(&env);
-
-    // Mock auth for maker
-    env.mock_all_auths();
-
-    // First cancellation should succeed
-    escrow.cancel(&env, &maker).unwrap();
-
-    // Second cancellation should fail
-    escrow.cancel(&env, &maker).unwrap();
+    let s = setup();
+    set_time(&s, 250);
+    s.escrow.cancel(&s.taker);
+    assert_eq!(s.escrow.try_cancel(&s.taker), Err(Ok(Error::AlreadyCancelled)));
+    set_time(&s, 310);
+    assert_eq!(s.escrow.try_public_cancel(&s.taker), Err(Ok(Error::AlreadyCancelled)));
 }
 
 #[test]
-#[should_panic(expected = "AlreadyWithdrawn")]
 fn test_cancel_after_withdrawal() {
-    let env = Env::default();
-    let (escrow, maker, _, _, _, hash_lock, _, _) = setup_escrow(&env);
-
-    // Mock auth for maker and taker
-    env.mock_all_auths();
+    let s = setup();
+    set_time(&s, 130);
+    s.escrow.withdraw(&s.secret);
+    set_time(&s, 250);
+    assert_eq!(s.escrow.try_cancel(&s.taker), Err(Ok(Error::AlreadyWithdrawn)));
+}
 
-    // First withdraw
-    let secret = BytesN::from_array(&env, &[3u8; 32]);
-    env.mock_crypto().keccak256.return_value = hash_lock;
-    escrow.withdraw(&env, &secret).unwrap();
+#[test]
+fn test_get_revealed_secret() {
+    let s = setup();
+    assert_eq!(s.escrow.try_get_revealed_secret(), Err(Ok(Error::InvalidTime)));
 
-    // Then try to cancel - should fail
-    escrow.cancel(&env, &maker).unwrap();
+    set_time(&s, 130);
+    s.escrow.withdraw(&s.secret);
+    assert_eq!(s.escrow.get_revealed_secret(), s.secret);
 }
 
 #[test]
-fn test_get_revealed_secret() {
-    let env = Env::default();
-    let (escrow, _, _, _, _, hash_lock, _, _) = setup_escrow(&env);
+fn test_native_escrow_withdraw() {
+    let env = env_with_accounts(&[([7; 32], 100 * XLM), ([9; 32], 100 * XLM)]);
+    let (maker, taker) = (account(&env, [7; 32]), account(&env, [9; 32]));
+    let xlm = native_sac(&env);
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
+    let secret = BytesN::from_array(&env, &[3u8; 32]);
 
-    // Mock auth for taker
-    env.mock_all_auths();
+    escrow.initialize(&escrow_params(&env, &maker, &taker, &xlm.address, 10 * XLM, &secret));
+    escrow.deposit();
+    xlm.transfer(&taker, &escrow.address, &XLM);
+    assert_eq!(xlm.balance(&escrow.address), 11 * XLM);
 
-    // Create a valid secret
+    env.ledger().with_mut(|li| li.timestamp = 130);
+    escrow.withdraw(&secret);
+    assert_eq!(xlm.balance(&escrow.address), 0);
+    assert_eq!(xlm.balance(&maker), 100 * XLM);
+    assert_eq!(xlm.balance(&taker), 100 * XLM);
+}
+
+#[test]
+fn test_native_escrow_maker_keeps_minimum_reserve() {
+    let env = env_with_accounts(&[([7; 32], 11 * XLM), ([9; 32], 100 * XLM)]);
+    let (maker, taker) = (account(&env, [7; 32]), account(&env, [9; 32]));
+    let xlm = native_sac(&env);
     let secret = BytesN::from_array(&env, &[3u8; 32]);
-    
-    // Mock the keccak256 function
-    env.mock_crypto().keccak256.return_value = hash_lock;
 
-    // Withdraw to reveal secret
-    escrow.withdraw(&env, &secret).unwrap();
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
+    let result = escrow.try_initialize(&escrow_params(&env, &maker, &taker, &xlm.address, 11 * XLM - MIN_ACCOUNT_BALANCE + 1, &secret));
+    assert_eq!(result, Err(Ok(Error::NativeReserveNotMet)));
+    let result = escrow.try_initialize(&escrow_params(&env, &maker, &taker, &xlm.address, 12 * XLM, &secret));
+    assert_eq!(result, Err(Ok(Error::InsufficientBalance)));
 
-    // Get revealed secret should succeed
-    let revealed_secret = escrow.get_revealed_secret(&env).unwrap();
-    assert_eq!(revealed_secret, secret);
+    escrow.initialize(&escrow_params(&env, &maker, &taker, &xlm.address, 11 * XLM - MIN_ACCOUNT_BALANCE, &secret));
+    escrow.deposit();
+    assert_eq!(xlm.balance(&maker), MIN_ACCOUNT_BALANCE);
 }
 
 #[test]
-#[should_panic(expected = "InvalidTime")]
-fn test_get_revealed_secret_before_withdrawal() {
-    let env = Env::default();
-    let (escrow, _, _, _, _, _, _, _) = setup_escrow(&env);
+fn test_native_escrow_recipient_must_exist() {
+    // The taker has no account to receive the safety deposit in
+    let env = env_with_accounts(&[([7; 32], 100 * XLM)]);
+    let (maker, taker) = (account(&env, [7; 32]), account(&env, [9; 32]));
+    let xlm = native_sac(&env);
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
+    let secret = BytesN::from_array(&env, &[3u8; 32]);
+
+    escrow.initialize(&escrow_params(&env, &maker, &taker, &xlm.address, 10 * XLM, &secret));
+    escrow.deposit();
+    xlm.transfer(&maker, &escrow.address, &XLM);
 
-    // Try to get revealed secret before withdrawal - should fail
-    escrow.get_revealed_secret(&env).unwrap();
+    env.ledger().with_mut(|li| li.timestamp = 130);
+    assert_eq!(escrow.try_withdraw(&secret), Err(Ok(Error::NativeReserveNotMet)));
+    assert!(!escrow.is_withdrawn_status());
 }
 
 #[test]
-fn test_keccak256() {
-    let env = Env::default();
-    let escrow = FusionPlusEscrow;
-    let data = BytesN::from_array(&env, &[1u8; 32]);
-    let expected_hash = BytesN::from_array(&env, &[2u8; 32]);
-
-    // Mock the keccak256 function
-    env.mock_crypto().keccak256.return_value = expected_hash;
-
-    // Test keccak256 function
-    let result = escrow.keccak256(&env, &data);
-    assert_eq!(result, expected_hash);
-}
\ No newline at end of file
+fn test_issued_asset_escrow_has_no_reserve() {
+    let env = env_with_accounts(&[]);
+    let issued = env.register_stellar_asset_contract(Address::generate(&env));
+    // Contract makers need no trustline and lock their whole balance
+    let (maker, taker) = (Address::generate(&env), Address::generate(&env));
+    token::StellarAssetClient::new(&env, &issued).mint(&maker, &(10 * XLM));
+    let escrow = FusionPlusEscrowClient::new(&env, &env.register_contract(None, FusionPlusEscrow));
+    let secret = BytesN::from_array(&env, &[3u8; 32]);
+
+    escrow.initialize(&escrow_params(&env, &maker, &taker, &issued, 10 * XLM, &secret));
+    escrow.deposit();
+    let issued = token::Client::new(&env, &issued);
+    assert_eq!(issued.balance(&maker), 0);
+    assert_eq!(issued.balance(&escrow.address), 10 * XLM);
+}
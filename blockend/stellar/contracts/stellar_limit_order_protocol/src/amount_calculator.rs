@@ -0,0 +1,41 @@
+//! Fill amounts at the order's price raised by a rate bump (Stellar counterpart of the EVM
+//! `AmountCalculatorLib` and the settlement extension's amount getters)
+//!
+//! Products of two u128 amounts are carried in 256 bits; the rate bump factor is applied to
+//! the division remainder, so nothing overflows while the result fits in u128.
+
+use soroban_sdk::{Env, U256};
+
+use crate::{auction::BASE_POINTS, Error, Order};
+
+/// Making amount for `taking_amount`, rounded down:
+/// `order.making_amount * taking_amount * BASE / (order.taking_amount * (BASE + rate_bump))`
+pub fn making_amount(env: &Env, order: &Order, taking_amount: u128, rate_bump: u128) -> Result<u128, Error> {
+    let product = U256::from_u128(env, order.making_amount).mul(&U256::from_u128(env, taking_amount));
+    let divisor = U256::from_u128(env, order.taking_amount).mul(&U256::from_u128(env, BASE_POINTS + rate_bump));
+    scale(env, &product, BASE_POINTS, &divisor, false)
+}
+
+/// Taking amount for `making_amount`, rounded up:
+/// `order.taking_amount * making_amount * (BASE + rate_bump) / (order.making_amount * BASE)`
+pub fn taking_amount(env: &Env, order: &Order, making_amount: u128, rate_bump: u128) -> Result<u128, Error> {
+    let product = U256::from_u128(env, order.taking_amount).mul(&U256::from_u128(env, making_amount));
+    let divisor = U256::from_u128(env, order.making_amount).mul(&U256::from_u128(env, BASE_POINTS));
+    scale(env, &product, BASE_POINTS + rate_bump, &divisor, true)
+}
+
+/// `product * factor / divisor` as `(product / divisor) * factor + (product % divisor) * factor / divisor`
+fn scale(env: &Env, product: &U256, factor: u128, divisor: &U256, round_up: bool) -> Result<u128, Error> {
+    let factor_256 = U256::from_u128(env, factor);
+    let remainder = product.rem_euclid(divisor).mul(&factor_256);
+    let mut fraction = remainder.div(divisor);
+    if round_up && remainder.rem_euclid(divisor) != U256::from_u32(env, 0) {
+        fraction = fraction.add(&U256::from_u32(env, 1));
+    }
+    product
+        .div(divisor)
+        .to_u128()
+        .and_then(|whole| whole.checked_mul(factor))
+        .and_then(|whole| whole.checked_add(fraction.to_u128()?))
+        .ok_or(Error::AmountOverflow)
+}
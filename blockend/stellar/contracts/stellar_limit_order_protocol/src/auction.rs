@@ -0,0 +1,70 @@
+//! Fusion Dutch auction (Stellar counterpart of the EVM settlement extension's `_getRateBump`)
+//!
+//! The auction details sit in the extension's making and taking amount data fields, in the
+//! EVM byte layout (big-endian):
+//!
+//! | Bytes  | Field                                                       |
+//! |--------|-------------------------------------------------------------|
+//! | 0..3   | gas bump estimate                                           |
+//! | 3..7   | gas price estimate                                          |
+//! | 7..11  | auction start time (unix seconds)                           |
+//! | 11..14 | auction duration (seconds)                                  |
+//! | 14..17 | initial rate bump                                           |
+//! | 17..   | points: 3-byte rate bump, 2-byte delay from previous point  |
+//!
+//! Rate bumps are in `1 / BASE_POINTS` units and raise the taking amount: a bump of
+//! `BASE_POINTS` asks the taker for twice the signed price. The bump is the initial one until
+//! the auction starts, then follows the points linearly and decays to zero at the end.
+//!
+//! Soroban contracts cannot read the network fee, so the fee is taken to match the estimate:
+//! the gas bump is the gas bump estimate whenever both gas fields are set.
+
+use soroban_sdk::Bytes;
+
+use crate::Error;
+
+pub const BASE_POINTS: u128 = 10_000_000;
+
+const HEADER_LEN: u32 = 17;
+const POINT_LEN: u32 = 5;
+
+/// Big-endian integer of `len` bytes at `offset`
+fn read(details: &Bytes, offset: u32, len: u32) -> u64 {
+    (offset..offset + len).fold(0u64, |acc, i| (acc << 8) | details.get_unchecked(i) as u64)
+}
+
+/// Rate bump of the auction at `timestamp`, net of the gas bump
+pub fn rate_bump(details: &Bytes, timestamp: u64) -> Result<u128, Error> {
+    if details.len() < HEADER_LEN || !(details.len() - HEADER_LEN).is_multiple_of(POINT_LEN) {
+        return Err(Error::InvalidExtension);
+    }
+    let (gas_bump_estimate, gas_price_estimate) = (read(details, 0, 3), read(details, 3, 4));
+    let gas_bump = if gas_price_estimate == 0 { 0 } else { gas_bump_estimate };
+    let start_time = read(details, 7, 4);
+    let finish_time = start_time + read(details, 11, 3);
+    let initial_rate_bump = read(details, 14, 3);
+
+    let auction_bump = if timestamp <= start_time {
+        initial_rate_bump
+    } else if timestamp >= finish_time {
+        0
+    } else {
+        let (mut point_time, mut point_bump) = (start_time, initial_rate_bump);
+        let mut offset = HEADER_LEN;
+        loop {
+            if offset == details.len() {
+                // Past the last point the bump decays linearly to zero at the finish time
+                break (finish_time - timestamp) * point_bump / (finish_time - point_time);
+            }
+            let next_bump = read(details, offset, 3);
+            let next_time = point_time + read(details, offset + 3, 2);
+            if timestamp <= next_time {
+                break ((timestamp - point_time) * next_bump + (next_time - timestamp) * point_bump)
+                    / (next_time - point_time);
+            }
+            (point_time, point_bump) = (next_time, next_bump);
+            offset += POINT_LEN;
+        }
+    };
+    Ok(auction_bump.saturating_sub(gas_bump) as u128)
+}
@@ -0,0 +1,60 @@
+//! Bit access over 256-bit words (EVM `uint256` values carried as `I256`/`U256`)
+
+use soroban_sdk::{Bytes, Env, I256, U256};
+
+/// Big-endian 256-bit word; bit 0 is the least significant bit of the last byte
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Word256(pub [u8; 32]);
+
+impl Word256 {
+    /// Two's-complement bytes of an `I256`. EVM flags live in the top bits, so a
+    /// `uint256` with bit 255 set arrives as a negative `I256`.
+    pub fn from_i256(value: &I256) -> Self {
+        Self::from_be_bytes(&value.to_be_bytes())
+    }
+
+    pub fn from_u256(value: &U256) -> Self {
+        Self::from_be_bytes(&value.to_be_bytes())
+    }
+
+    fn from_be_bytes(bytes: &Bytes) -> Self {
+        let mut word = [0u8; 32];
+        bytes.copy_into_slice(&mut word);
+        Word256(word)
+    }
+
+    pub fn to_i256(&self, env: &Env) -> I256 {
+        I256::from_be_bytes(env, &Bytes::from_array(env, &self.0))
+    }
+
+    pub fn to_u256(&self, env: &Env) -> U256 {
+        U256::from_be_bytes(env, &Bytes::from_array(env, &self.0))
+    }
+
+    pub fn bit(&self, index: u32) -> bool {
+        let byte = self.0[31 - (index / 8) as usize];
+        byte & (1 << (index % 8)) != 0
+    }
+
+    pub fn set_bit(&mut self, index: u32) {
+        self.0[31 - (index / 8) as usize] |= 1 << (index % 8);
+    }
+
+    pub fn or(&self, other: &Word256) -> Word256 {
+        let mut word = self.0;
+        for (byte, other) in word.iter_mut().zip(other.0.iter()) {
+            *byte |= other;
+        }
+        Word256(word)
+    }
+
+    /// `width` bits starting at `offset`, for fields of at most 128 bits
+    pub fn field(&self, offset: u32, width: u32) -> u128 {
+        (0..width).rev().fold(0u128, |acc, i| (acc << 1) | self.bit(offset + i) as u128)
+    }
+
+    /// Whether any of the `width` bits starting at `offset` is set
+    pub fn any(&self, offset: u32, width: u32) -> bool {
+        (offset..offset + width).any(|i| self.bit(i))
+    }
+}
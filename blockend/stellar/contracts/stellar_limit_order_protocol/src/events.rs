@@ -0,0 +1,55 @@
+//! Typed order events
+//!
+//! Topics are the event's symbol, the maker and the order hash, so indexers can filter fills
+//! and cancellations by maker or order without decoding the data.
+
+use soroban_sdk::{contracttype, Address, BytesN, Env, Symbol};
+
+use crate::Order;
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FillMode {
+    Making, // The taker's amount was a making amount
+    Taking, // The taker's amount was a taking amount
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrderFilled {
+    pub taker: Address,
+    pub maker_asset: Address,
+    pub taker_asset: Address,
+    pub making_amount: u128,
+    pub taking_amount: u128,      // Fees included
+    pub remaining: u128,          // Remaining making amount after the fill
+    pub mode: FillMode,
+    pub allowed_sender: Address,
+    pub integrator_fee: u128,
+    pub protocol_fee: u128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrderCancelled {
+    pub maker_asset: Address,
+    pub taker_asset: Address,
+    pub making_amount: u128,
+    pub taking_amount: u128,
+    pub remaining: u128,          // Remaining making amount the cancellation voided
+}
+
+pub fn order_filled(env: &Env, order: &Order, order_hash: &BytesN<32>, event: OrderFilled) {
+    env.events().publish((Symbol::new(env, "OrderFilled"), order.maker.clone(), order_hash.clone()), event);
+}
+
+pub fn order_cancelled(env: &Env, order: &Order, order_hash: &BytesN<32>, remaining: u128) {
+    let event = OrderCancelled {
+        maker_asset: order.maker_asset.clone(),
+        taker_asset: order.taker_asset.clone(),
+        making_amount: order.making_amount,
+        taking_amount: order.taking_amount,
+        remaining,
+    };
+    env.events().publish((Symbol::new(env, "OrderCancelled"), order.maker.clone(), order_hash.clone()), event);
+}
@@ -0,0 +1,123 @@
+//! Order extension: dynamic fields carried in `Order.interactions` (Stellar counterpart of the
+//! EVM `ExtensionLib`)
+//!
+//! `Order.offsets` packs the cumulative end offset of each field into `interactions`, 16 bits
+//! per field, field 0 in the lowest bits. A field starts where the previous one ends and is
+//! empty when both offsets are equal. Bytes after the last field are custom data.
+//!
+//! As bytes, an extension is the offsets word followed by the fields:
+//!
+//! | Bytes  | Field                                                                |
+//! |--------|----------------------------------------------------------------------|
+//! | 0..16  | offsets, big-endian u128                                             |
+//! | 16..   | `interactions`: the fields in `DynamicField` order, then custom data |
+//!
+//! An order carries its extension inline, committed by the order hash like any other field,
+//! or sets `HAS_EXTENSION` and leaves it to the taker's args. Where EVM commits to such an
+//! extension in the low 160 bits of the salt, the struct hash of a `HAS_EXTENSION` order takes
+//! `keccak256(extension)` in place of its interactions, so the order hash covers the whole
+//! extension and the order is hashed, cancelled or looked up with its extension attached.
+
+use soroban_sdk::{Bytes, Env};
+
+use crate::{Error, MakerTraits, Order};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum DynamicField {
+    MakingAmountData = 0,
+    TakingAmountData = 1,
+    Predicate = 2,
+    Permit = 3,
+    PreInteractionData = 4,
+    PostInteractionData = 5,
+    TwapData = 6,
+    FeeData = 7,
+}
+
+impl DynamicField {
+    pub const COUNT: u32 = 8;
+    pub const OFFSET_BITS: u32 = 16;
+    pub const OFFSET_MASK: u128 = 0xFFFF;
+}
+
+/// Length of the offsets word heading an extension
+pub const OFFSETS_LEN: u32 = 16;
+
+/// End offset of field `index` (fields past the last one end where it does)
+fn end_offset(offsets: u128, index: u32) -> u32 {
+    ((offsets >> (index * DynamicField::OFFSET_BITS)) & DynamicField::OFFSET_MASK) as u32
+}
+
+/// Bytes of a dynamic field, bounds-checked against `interactions`
+pub fn field(order: &Order, field: DynamicField) -> Result<Bytes, Error> {
+    let index = field as u32;
+    let begin = if index == 0 { 0 } else { end_offset(order.offsets, index - 1) };
+    let end = end_offset(order.offsets, index);
+    if begin > end || end > order.interactions.len() {
+        return Err(Error::InvalidExtension);
+    }
+    Ok(order.interactions.slice(begin..end))
+}
+
+/// Bytes after the last dynamic field
+pub fn custom_data(order: &Order) -> Result<Bytes, Error> {
+    let begin = end_offset(order.offsets, DynamicField::COUNT - 1);
+    if begin > order.interactions.len() {
+        return Err(Error::InvalidExtension);
+    }
+    Ok(order.interactions.slice(begin..))
+}
+
+/// Check that the fields of `offsets` follow each other within `len` bytes
+pub fn check_offsets(offsets: u128, len: u32) -> Result<(), Error> {
+    let mut begin = 0;
+    for index in 0..DynamicField::COUNT {
+        let end = end_offset(offsets, index);
+        if end < begin || end > len {
+            return Err(Error::InvalidExtension);
+        }
+        begin = end;
+    }
+    Ok(())
+}
+
+/// The order's inline extension as bytes; empty when it has none
+pub fn encode(env: &Env, order: &Order) -> Bytes {
+    if order.offsets == 0 && order.interactions.is_empty() {
+        return Bytes::new(env);
+    }
+    let mut extension = Bytes::from_array(env, &order.offsets.to_be_bytes());
+    extension.append(&order.interactions);
+    extension
+}
+
+/// Split an extension into its offsets and fields
+pub fn decode(extension: &Bytes) -> Result<(u128, Bytes), Error> {
+    if extension.len() < OFFSETS_LEN {
+        return Err(Error::InvalidExtension);
+    }
+    let mut word = [0u8; OFFSETS_LEN as usize];
+    extension.slice(..OFFSETS_LEN).copy_into_slice(&mut word);
+    let offsets = u128::from_be_bytes(word);
+    let interactions = extension.slice(OFFSETS_LEN..);
+    check_offsets(offsets, interactions.len())?;
+    Ok((offsets, interactions))
+}
+
+/// Attach the extension a taker passes in args to a `HAS_EXTENSION` order. With no extension
+/// in args the order is taken as it is, its extension already attached if it has one. Orders
+/// without `HAS_EXTENSION` take no extension from args.
+pub fn attach(mut order: Order, extension: &Bytes) -> Result<Order, Error> {
+    if extension.is_empty() {
+        return Ok(order);
+    }
+    if !MakerTraits(order.maker_traits).has_extension() {
+        return Err(Error::UnexpectedOrderExtension);
+    }
+    if order.offsets != 0 || !order.interactions.is_empty() {
+        return Err(Error::InvalidExtension);
+    }
+    (order.offsets, order.interactions) = decode(extension)?;
+    Ok(order)
+}
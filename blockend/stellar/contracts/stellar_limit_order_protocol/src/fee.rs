@@ -0,0 +1,96 @@
+//! Integrator and protocol fees (Stellar counterpart of the EVM `FeeTaker` extension)
+//!
+//! The fee data field holds, big-endian:
+//!
+//! | Bytes          | Field                                                            |
+//! |----------------|------------------------------------------------------------------|
+//! | 0..2           | integrator fee (bps)                                             |
+//! | 2..4           | protocol fee (bps)                                               |
+//! | 4..6           | protocol fee discount for whitelisted resolvers (bps of the fee) |
+//! | 6              | whitelist length `n`                                             |
+//! | 7..7 + 10n     | whitelist: last 10 bytes of each resolver's address payload      |
+//! | then           | integrator fee recipient, length-prefixed address XDR            |
+//! | then           | protocol fee recipient, length-prefixed address XDR              |
+//!
+//! Fees are charged to the taker on top of the order's price: the taking amount of a fill
+//! includes them, as it does on EVM. Out of a taking amount `t` each recipient gets
+//! `t * fee / (BASE + total fees)` rounded down, and the order's receiver the rest, so the
+//! parts add up to `t` exactly.
+
+use soroban_sdk::{Address, Bytes, Env, U256};
+
+use crate::{Error, StellarLimitOrderProtocol};
+
+pub const FEE_BASE: u128 = 10_000;
+
+const HEADER_LEN: u32 = 7;
+const WHITELIST_ENTRY_LEN: u32 = 10;
+
+/// Fees of a fill, with the resolver discount applied
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Fees {
+    pub integrator: Address,
+    pub integrator_fee: u128,
+    pub protocol: Address,
+    pub protocol_fee: u128,
+}
+
+impl Fees {
+    /// Parse the fee data for a fill by `taker`; `None` quotes the fees of a resolver off the whitelist
+    pub fn parse(env: &Env, details: &Bytes, taker: Option<&Address>) -> Result<Fees, Error> {
+        if details.len() < HEADER_LEN {
+            return Err(Error::InvalidExtension);
+        }
+        let read_u16 = |offset: u32| ((details.get_unchecked(offset) as u128) << 8) | details.get_unchecked(offset + 1) as u128;
+        let (integrator_fee, protocol_fee, discount) = (read_u16(0), read_u16(2), read_u16(4));
+        if integrator_fee > FEE_BASE || protocol_fee > FEE_BASE || discount > FEE_BASE {
+            return Err(Error::InvalidExtension);
+        }
+        let whitelist_end = HEADER_LEN + details.get_unchecked(6) as u32 * WHITELIST_ENTRY_LEN;
+        if whitelist_end > details.len() {
+            return Err(Error::InvalidExtension);
+        }
+        let whitelisted = taker.is_some_and(|taker| {
+            let payload = StellarLimitOrderProtocol::address_to_bytes(env, taker);
+            let suffix = payload.slice(payload.len() - WHITELIST_ENTRY_LEN..);
+            (HEADER_LEN..whitelist_end)
+                .step_by(WHITELIST_ENTRY_LEN as usize)
+                .any(|entry| details.slice(entry..entry + WHITELIST_ENTRY_LEN) == suffix)
+        });
+        let (integrator, recipients) = StellarLimitOrderProtocol::split_address(env, &details.slice(whitelist_end..)).ok_or(Error::InvalidExtension)?;
+        let (protocol, rest) = StellarLimitOrderProtocol::split_address(env, &recipients).ok_or(Error::InvalidExtension)?;
+        if !rest.is_empty() {
+            return Err(Error::InvalidExtension);
+        }
+        let protocol_fee = if whitelisted { protocol_fee * (FEE_BASE - discount) / FEE_BASE } else { protocol_fee };
+        Ok(Fees { integrator, integrator_fee, protocol, protocol_fee })
+    }
+
+    pub fn total(&self) -> u128 {
+        self.integrator_fee + self.protocol_fee
+    }
+
+    /// Integrator and protocol shares of a taking amount
+    pub fn split(&self, env: &Env, taking_amount: u128) -> (u128, u128) {
+        let share = |fee: u128| {
+            let product = U256::from_u128(env, taking_amount).mul(&U256::from_u128(env, fee));
+            // Below `taking_amount`, so it fits
+            product.div(&U256::from_u128(env, FEE_BASE + self.total())).to_u128().unwrap()
+        };
+        (share(self.integrator_fee), share(self.protocol_fee))
+    }
+}
+
+/// `amount` plus `fee` bps, rounded up
+pub fn with_fee(env: &Env, amount: u128, fee: u128) -> Result<u128, Error> {
+    let product = U256::from_u128(env, amount).mul(&U256::from_u128(env, FEE_BASE + fee));
+    let base = U256::from_u128(env, FEE_BASE);
+    let rounding = U256::from_u128(env, FEE_BASE - 1);
+    product.add(&rounding).div(&base).to_u128().ok_or(Error::AmountOverflow)
+}
+
+/// The amount that `with_fee` raises to at most `amount`, rounded down
+pub fn without_fee(env: &Env, amount: u128, fee: u128) -> u128 {
+    let product = U256::from_u128(env, amount).mul(&U256::from_u128(env, FEE_BASE));
+    product.div(&U256::from_u128(env, FEE_BASE + fee)).to_u128().unwrap()
+}
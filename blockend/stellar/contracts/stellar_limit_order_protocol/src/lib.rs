@@ -1,9 +1,33 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, Address, Bytes, BytesN, Env, Map, Symbol, String, xdr::{ScErrorCode, ScErrorType}, I256, IntoVal, vec, Vec,
+    contract, contracterror, contractimpl, contracttype, symbol_short, Address, Bytes, BytesN, Env, Symbol, String, I256, U256, IntoVal, vec, Vec,
 };
 use soroban_sdk::token;
-use soroban_sdk::xdr::ToXdr;
+use fee::Fees;
+use order_group::OrderGroup;
+use soroban_sdk::xdr::{FromXdr, ToXdr};
+
+mod amount_calculator;
+mod auction;
+mod bits;
+mod events;
+mod extension;
+mod fee;
+mod maker_traits;
+mod native;
+mod order_book;
+mod order_group;
+mod predicate;
+mod remaining_invalidator;
+mod taker_traits;
+mod twap;
+pub use bits::Word256;
+pub use events::{FillMode, OrderCancelled, OrderFilled};
+pub use extension::DynamicField;
+pub use maker_traits::MakerTraits;
+pub use order_book::{ActiveOrder, OrderIndex, OrderPage, PostedOrder};
+pub use remaining_invalidator::{OrderStatus, RemainingInvalidator};
+pub use taker_traits::TakerTraits;
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -12,12 +36,14 @@ pub struct Order {
     pub maker_asset: Address,     // Stellar asset contract
     pub taker_asset: Address,     // Stellar asset contract  
     pub maker: Address,           // Stellar account
-    pub receiver: Address,        // Stellar account
-    pub allowed_sender: Address,  // Zero for public orders
+    pub receiver: Address,        // Receives the taking amount; the zero address for the maker
+    pub allowed_sender: Address,  // Only taker allowed to fill; `any_sender()` for public orders
     pub making_amount: u128,
     pub taking_amount: u128,
-    pub offsets: u64,             // Encodes lengths of dynamic data
+    pub maker_traits: u128,       // MakerTraits (see maker_traits.rs)
+    pub offsets: u128,            // End offsets of the dynamic fields (see extension.rs)
     pub interactions: Bytes,      // Dynamic fields (predicate, etc.)
+    pub evm_signer: BytesN<20>,   // EVM address signing for the maker; zero for Stellar-signed orders
 }
 
 // Resolver-compatible Order structure (simplified version)
@@ -48,11 +74,60 @@ pub struct FactoryOrder {
     pub maker_traits: u128,       // MakerTraits as uint256
 }
 
+// One order of a `fill_orders` batch, with the arguments of `fill_order_args`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FillRequest {
+    pub order: Order,
+    pub signature: Bytes,
+    pub amount: u128,
+    pub taker_traits: TakerTraits,
+    pub args: Bytes,
+}
+
+// Outcome of one order of a batch; `error` is the `Error` code, 0 when the order was filled
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct TakerTraits {
-    pub threshold: u128,
-    pub skip_maker_permit: bool,
+pub struct FillResult {
+    pub order_hash: BytesN<32>,
+    pub making_amount: u128,
+    pub taking_amount: u128,
+    pub error: u32,
+}
+
+// Amounts a fill would settle (see `quote_fill`)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FillQuote {
+    pub order_hash: BytesN<32>,
+    pub making_amount: u128,
+    pub taking_amount: u128,      // Fees included
+    pub remaining: u128,          // Remaining making amount after the fill
+    pub integrator_fee: u128,
+    pub protocol_fee: u128,
+    pub payouts: Vec<(Address, u128)>, // Taker payments: the receiver's share, then the fees
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BatchMode {
+    AllOrNothing, // The first failing order fails the batch
+    BestEffort,   // Failing orders are skipped and reported
+}
+
+// Per-order storage keys, each a persistent entry with its own TTL
+#[contracttype]
+#[derive(Clone)]
+pub enum DataKey {
+    RemainingInvalidator(BytesN<32>), // Fill state of a filled or cancelled order (see remaining_invalidator.rs)
+    RegisteredOrder(BytesN<32>),  // Order pre-approved on-chain by its maker
+    EvmSigner(Address),           // EVM address allowed to sign orders funded by this maker
+    BitInvalidator(Address, u32), // Maker's used/cancelled nonces, 256 per slot
+    Epoch(Address, u64),          // Maker's current epoch for a series
+    PostedOrder(BytesN<32>),      // Order in the on-chain order book (see order_book.rs)
+    IndexLength(OrderIndex),      // Number of slots of an order book index
+    IndexSlot(OrderIndex, u32),   // Order hash in an order book index slot
+    GroupConsumer(Address, BytesN<32>), // (maker, group id): order that consumed a one-cancels-other group (see order_group.rs)
 }
 
 #[contract]
@@ -60,206 +135,947 @@ pub struct StellarLimitOrderProtocol;
 
 #[contractimpl]
 impl StellarLimitOrderProtocol {
-    const REMAINING_INVALIDATOR: Symbol = symbol_short!("rem_inv");
-    const ORDERS: Symbol = symbol_short!("orders");
     const FACTORY: Symbol = symbol_short!("factory");  // Add factory storage
 
     // Constants matching EVM side
-    const ORDER_TYPE_HASH: &'static [u8] = b"Order(uint256 salt,address makerAsset,address takerAsset,address maker,address receiver,address allowedSender,uint256 makingAmount,uint256 takingAmount,uint256 offsets,bytes interactions)";
+    // Stellar addresses are encoded as bytes32: the account key or contract id behind the strkey.
+    // The domain uses `salt` for the LOP contract id since `verifyingContract` must be 20 bytes.
+    const ORDER_TYPE: &'static [u8] = b"Order(uint256 salt,bytes32 makerAsset,bytes32 takerAsset,bytes32 maker,bytes32 receiver,bytes32 allowedSender,uint256 makingAmount,uint256 takingAmount,uint256 makerTraits,uint256 offsets,bytes interactions,address evmSigner)";
+    const DOMAIN_TYPE: &'static [u8] = b"EIP712Domain(string name,string version,bytes32 salt)";
     const DOMAIN_NAME: &'static [u8] = b"1inch Limit Order Protocol";
     const DOMAIN_VERSION: &'static [u8] = b"4";
 
+    // XDR layout of an account address: SCV_ADDRESS (18), SC_ADDRESS_TYPE_ACCOUNT (0),
+    // PUBLIC_KEY_TYPE_ED25519 (0), followed by the raw 32-byte key
+    const ACCOUNT_XDR_HEADER: [u8; 12] = [0, 0, 0, 18, 0, 0, 0, 0, 0, 0, 0, 0];
+    const ACCOUNT_XDR_LEN: u32 = 44;
+    const EVM_SIGNATURE_LEN: u32 = 65;
+    const TARGET_LENGTH_PREFIX: u32 = 4;
+    // Account with the all-zero key: hashes as a zero bytes32, the EVM's address(0). Stands for
+    // any sender in `allowed_sender` and for the maker in `receiver`.
+    const ZERO_ADDRESS: &'static str = "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF";
+
+    // Persistent entries are extended to 30 days whenever written with less than 7 days left
+    const DAY_IN_LEDGERS: u32 = 17_280;
+    const PERSISTENT_TTL_THRESHOLD: u32 = 7 * Self::DAY_IN_LEDGERS;
+    const PERSISTENT_TTL_EXTEND_TO: u32 = 30 * Self::DAY_IN_LEDGERS;
+
     /// Initialize the contract
     pub fn initialize(env: &Env, factory: Address) -> Result<(), Error> {
-        env.storage().instance().set(&Self::REMAINING_INVALIDATOR, &Map::<BytesN<32>, u128>::new(env));
-        env.storage().instance().set(&Self::ORDERS, &Map::<BytesN<32>, Order>::new(env));
         env.storage().instance().set(&Self::FACTORY, &factory);  // Store factory address
         Ok(())
     }
 
     /// Fill an order (equivalent to EVM fillOrder)
+    ///
+    /// `signature` is the maker's ed25519 signature over the order hash. Pass an empty
+    /// signature for Soroban-auth orders (see `authorize_order`). The making amount is pulled
+    /// from the maker against its allowance to this contract; the taker, who must authorize
+    /// the call, pays the taking amount.
     pub fn fill_order(
         env: &Env,
         order: Order,
         signature: Bytes,
         taker: Address,
         amount: u128,
-        _taker_traits: TakerTraits,
+        taker_traits: TakerTraits,
+    ) -> Result<(u128, u128, BytesN<32>), Error> {
+        Self::fill_order_args(env, order, signature, taker, amount, taker_traits, Bytes::new(env))
+    }
+
+    /// Fill an order with taker args (equivalent to EVM fillOrderArgs)
+    ///
+    /// `args` holds, in order: the target receiving the making amount when `args_has_target`
+    /// is set, `args_extension_length` bytes of extension and `args_interaction_length` bytes
+    /// of taker interaction (see `decode_args`). The taker authorizes the call with its args,
+    /// so nobody else can redirect the making amount under the taker's payment.
+    pub fn fill_order_args(
+        env: &Env,
+        order: Order,
+        signature: Bytes,
+        taker: Address,
+        amount: u128,
+        taker_traits: TakerTraits,
+        args: Bytes,
     ) -> Result<(u128, u128, BytesN<32>), Error> {
+        taker.require_auth();
+        let args = Self::decode_args(env, &taker_traits, &args, &taker)?;
+        Self::fill(env, order, signature, &taker, &args, amount, taker_traits)
+    }
+
+    /// Fill several orders for one taker
+    ///
+    /// `AllOrNothing` fails the whole batch with the first error; `BestEffort` skips orders
+    /// that fail validation and reports their error code. Orders are checked before anything
+    /// is written, so a skipped order leaves no trace, but traps (a bad ed25519 signature, a
+    /// failing transfer or listener) still fail the batch. Transfers of orders without
+    /// interactions are summed per token, sender and recipient and made once at the end;
+    /// orders with interactions settle inline so their listeners see the usual sequence.
+    /// The taker authorizes the whole batch.
+    pub fn fill_orders(env: &Env, taker: Address, requests: Vec<FillRequest>, mode: BatchMode) -> Result<Vec<FillResult>, Error> {
+        taker.require_auth();
+        let mut transfers = Transfers::new(env);
+        let mut results = Vec::new(env);
+        for request in requests.iter() {
+            let args = Self::decode_args(env, &request.taker_traits, &request.args, &taker);
+            let attached = args.as_ref().ok().and_then(|args| extension::attach(request.order.clone(), &args.extension).ok());
+            let order_hash = Self::hash_order(env, attached.unwrap_or(request.order.clone()));
+            let prepared = args.and_then(|args| {
+                let prepared = Self::prepare_fill(env, request.order, request.signature, &taker, &args, request.amount, request.taker_traits, true)?;
+                Ok((prepared, args))
+            });
+            let result = match prepared {
+                Ok((prepared, args)) => {
+                    Self::execute_fill(env, &prepared, &args, Some(&mut transfers));
+                    FillResult { order_hash, making_amount: prepared.fill.making_amount, taking_amount: prepared.fill.taking_amount, error: 0 }
+                }
+                Err(error) if mode == BatchMode::BestEffort => FillResult { order_hash, making_amount: 0, taking_amount: 0, error: error as u32 },
+                Err(error) => return Err(error),
+            };
+            results.push_back(result);
+        }
+        transfers.settle(env);
+        Ok(results)
+    }
+
+    /// Quote a fill without executing it: runs every check of `fill_order_args` for `taker`
+    /// and returns the amounts the fill would settle, or the error it would fail with.
+    ///
+    /// Nothing is written or transferred and no Soroban auth is required, so Soroban-auth
+    /// orders are quoted as if the maker approved the fill. As in `fill_order`, an ed25519
+    /// signature that does not verify traps instead of returning `BadSignature`.
+    /// `args` is decoded as the fill decodes it, so the same traits and args quote and fill alike.
+    #[allow(clippy::too_many_arguments)]
+    pub fn quote_fill(
+        env: &Env,
+        order: Order,
+        signature: Bytes,
+        taker: Address,
+        amount: u128,
+        taker_traits: TakerTraits,
+        args: Bytes,
+    ) -> Result<FillQuote, Error> {
+        let args = Self::decode_args(env, &taker_traits, &args, &taker)?;
+        Self::quote(env, order, signature, &taker, &args, amount, taker_traits)
+    }
+
+    /// Quote a resolver fill without executing it, as `quote_fill` does for `fill_args`.
+    /// Resolvers authorize the quoted payouts before filling, since the transfers are made
+    /// below their call to this contract.
+    #[allow(clippy::too_many_arguments)]
+    pub fn quote_args(
+        env: &Env,
+        resolver_order: ResolverOrder,
+        signature: Bytes,
+        taker: Address,
+        amount: u128,
+        taker_traits: I256,
+        args: Bytes,
+    ) -> Result<FillQuote, Error> {
+        let order = Self::convert_resolver_order(env, resolver_order, &signature)?;
+        let taker_traits = Self::convert_taker_traits(env, &taker_traits)?;
+        let args = Self::decode_args(env, &taker_traits, &args, &taker)?;
+        Self::quote(env, order, signature, &taker, &args, amount, taker_traits)
+    }
+
+    /// Shared quote logic: a fill prepared without authorization
+    #[allow(clippy::too_many_arguments)]
+    fn quote(env: &Env, order: Order, signature: Bytes, taker: &Address, args: &FillArgs, amount: u128, taker_traits: TakerTraits) -> Result<FillQuote, Error> {
+        let prepared = Self::prepare_fill(env, order, signature, taker, args, amount, taker_traits, false)?;
+        let Fill { order_hash, making_amount, taking_amount, remaining, .. } = prepared.fill;
+        let (integrator_fee, protocol_fee) = prepared.fee_amounts;
+        let payouts = prepared.payouts;
+        Ok(FillQuote { order_hash, making_amount, taking_amount, remaining: remaining - making_amount, integrator_fee, protocol_fee, payouts })
+    }
+
+    /// Shared fill logic: `taker` pays the taking amount, `args.target` receives the making amount
+    fn fill(
+        env: &Env,
+        order: Order,
+        signature: Bytes,
+        taker: &Address,
+        args: &FillArgs,
+        amount: u128,
+        taker_traits: TakerTraits,
+    ) -> Result<(u128, u128, BytesN<32>), Error> {
+        let prepared = Self::prepare_fill(env, order, signature, taker, args, amount, taker_traits, true)?;
+        Self::execute_fill(env, &prepared, args, None);
+        Ok((prepared.fill.making_amount, prepared.fill.taking_amount, prepared.fill.order_hash))
+    }
+
+    /// Validate a fill and compute its amounts and listeners without writing anything.
+    /// The maker's Soroban auth is only required when `authorize` is set; the taker's is
+    /// required by the entry points.
+    #[allow(clippy::too_many_arguments)]
+    fn prepare_fill(
+        env: &Env,
+        order: Order,
+        signature: Bytes,
+        taker: &Address,
+        args: &FillArgs,
+        amount: u128,
+        taker_traits: TakerTraits,
+        authorize: bool,
+    ) -> Result<PreparedFill, Error> {
         // Validate order
         Self::validate_order(&order)?;
         
-        // Check signature
-        Self::verify_signature(env, &order, &signature)?;
-        
         // Calculate order hash (matches EVM exactly)
+        // Take the extension from the taker's args; the order hash commits to it
+        let order = extension::attach(order, &args.extension)?;
         let order_hash = Self::hash_order(env, order.clone());
         
-        // Check remaining amount
-        let remaining = Self::get_remaining_amount(env, &order_hash);
-        if remaining < amount {
-            return Err(Error::TakingAmountExceeded);
+        // Check signature or Soroban authorization
+        Self::authorize_order(env, &order, &order_hash, &signature, amount, authorize)?;
+        
+        // Check maker traits and the remaining making amount. Bit-invalidator orders are
+        // consumed by their first fill, so they always start from the full amount.
+        Self::check_maker_traits(env, &order, taker)?;
+        Self::check_predicate(env, &order)?;
+        let group = order_group::of(&order)?;
+        if let Some(group) = &group {
+            Self::check_group(env, group, &order_hash)?;
+        }
+        let traits = MakerTraits(order.maker_traits);
+        let remaining = if traits.use_bit_invalidator() {
+            order.making_amount
+        } else {
+            Self::get_remaining_amount(env, &order, &order_hash)?
+        };
+        if remaining == 0 {
+            return Err(Error::InvalidatedOrder);
+        }
+        let available = Self::available_amount(env, &order, remaining)?;
+        if available == 0 {
+            return Err(Error::TimeSliceExhausted);
         }
         
-        // Calculate making and taking amounts
-        let making_amount = (amount * order.making_amount) / order.taking_amount;
-        let taking_amount = amount;
+        // Calculate making and taking amounts, fees included
+        let fees = Self::order_fees(env, &order, Some(taker))?;
+        let fee_bps = fees.as_ref().map_or(0, Fees::total);
+        let (making_amount, taking_amount) = Self::fill_amounts(env, &order, amount, &taker_traits, available, fee_bps)?;
+        if !traits.allow_partial_fills() && making_amount != order.making_amount {
+            return Err(Error::PartialFillNotAllowed);
+        }
+        if making_amount == 0 || taking_amount == 0 {
+            return Err(Error::SwapWithZeroAmount);
+        }
         
-        // Update remaining amount
-        Self::update_remaining_amount(env, &order_hash, remaining - amount);
+        // Resolve the interaction listeners
+        let pre_interaction = if traits.need_pre_interaction_call() {
+            let data = extension::field(&order, DynamicField::PreInteractionData)?;
+            Some(if data.is_empty() {
+                (order.maker.clone(), data)
+            } else {
+                Self::split_address(env, &data).ok_or(Error::InvalidExtension)?
+            })
+        } else {
+            None
+        };
+        let taker_interaction = if args.interaction.is_empty() {
+            None
+        } else {
+            Some(Self::split_address(env, &args.interaction).ok_or(Error::InvalidArgs)?)
+        };
+        let post_interaction = if traits.need_post_interaction_call() {
+            let data = extension::field(&order, DynamicField::PostInteractionData)?;
+            Some(if data.is_empty() {
+                (Self::factory(env)?, args.extra.clone())
+            } else {
+                Self::split_address(env, &data).ok_or(Error::InvalidExtension)?
+            })
+        } else {
+            None
+        };
         
-        // Transfer assets (REAL IMPLEMENTATION)
-        Self::transfer_assets(env, &order, &taker, making_amount, taking_amount)?;
+        // Split the taking amount between the receiver and the fee recipients
+        let (integrator_fee, protocol_fee) = fees.as_ref().map_or((0, 0), |fees| fees.split(env, taking_amount));
+        let mut payouts = vec![env, (Self::receiver(env, &order), taking_amount - integrator_fee - protocol_fee)];
+        if let Some(fees) = fees {
+            payouts.push_back((fees.integrator, integrator_fee));
+            payouts.push_back((fees.protocol, protocol_fee));
+        }
         
-        // Store order for reference
-        let mut orders: Map<BytesN<32>, Order> = env.storage().instance().get(&Self::ORDERS).unwrap_or(Map::new(env));
-        orders.set(order_hash.clone(), order);
-        env.storage().instance().set(&Self::ORDERS, &orders);
+        // Native XLM legs: the unwrap flags pin the asset, every native leg keeps its reserves
+        native::check_flags(env, &order, &taker_traits)?;
+        native::check_transfer(env, &order.maker_asset, &order.maker, making_amount, core::iter::once(args.target.clone()))?;
+        let paid = payouts.iter().filter(|(_, amount)| *amount > 0).map(|(recipient, _)| recipient);
+        native::check_transfer(env, &order.taker_asset, taker, taking_amount, paid)?;
         
-        // Emit OrderFilled event
-        env.events().publish(("OrderFilled",), (order_hash.clone(), remaining - amount));
+        let fill = Fill { order_hash, taker: taker.clone(), making_amount, taking_amount, remaining };
+        let mode = if taker_traits.maker_amount { FillMode::Making } else { FillMode::Taking };
+        Ok(PreparedFill { order, fill, mode, group, payouts, fee_amounts: (integrator_fee, protocol_fee), pre_interaction, taker_interaction, post_interaction })
+    }
+
+    /// Record a prepared fill, run its interactions and move its funds
+    ///
+    /// Interactions run as on EVM: the maker's pre-interaction before any transfer, the
+    /// taker's interaction between the maker and the taker legs, and the maker's
+    /// post-interaction last. Fills without interactions add their transfers to `batch`
+    /// when given one.
+    fn execute_fill(env: &Env, prepared: &PreparedFill, args: &FillArgs, batch: Option<&mut Transfers>) {
+        let PreparedFill { order, fill, .. } = prepared;
+        
+        // Update remaining amount and consume the nonce
+        let traits = MakerTraits(order.maker_traits);
+        if traits.use_bit_invalidator() {
+            Self::invalidate_nonce(env, &order.maker, traits.nonce_or_epoch());
+        }
+        Self::update_remaining_amount(env, &fill.order_hash, RemainingInvalidator::remains(fill.remaining - fill.making_amount));
+        if let Some(group) = &prepared.group {
+            Self::consume_group(env, group, &fill.order_hash, fill.remaining - fill.making_amount);
+        }
         
-        Ok((making_amount, taking_amount, order_hash))
+        // Interactions and transfers
+        let has_interactions = prepared.pre_interaction.is_some() || prepared.taker_interaction.is_some() || prepared.post_interaction.is_some();
+        match batch {
+            Some(batch) if !has_interactions => {
+                batch.pull(&order.maker_asset, &order.maker, &args.target, fill.making_amount);
+                for (recipient, amount) in prepared.payouts.iter() {
+                    batch.push(&order.taker_asset, &fill.taker, &recipient, amount);
+                }
+            }
+            _ => {
+                if let Some((listener, extra_data)) = &prepared.pre_interaction {
+                    Self::call_interaction(env, listener, "pre_interaction", order, fill, extra_data.clone());
+                }
+                Self::transfer_maker_asset(env, order, &args.target, fill.making_amount);
+                if let Some((listener, extra_data)) = &prepared.taker_interaction {
+                    Self::call_interaction(env, listener, "taker_interaction", order, fill, extra_data.clone());
+                }
+                Self::transfer_taker_asset(env, order, &fill.taker, &prepared.payouts);
+                if let Some((listener, extra_data)) = &prepared.post_interaction {
+                    Self::call_interaction(env, listener, "post_interaction", order, fill, extra_data.clone());
+                }
+            }
+        }
+        
+        // Emit OrderFilled event
+        let (integrator_fee, protocol_fee) = prepared.fee_amounts;
+        let event = OrderFilled {
+            taker: fill.taker.clone(),
+            maker_asset: order.maker_asset.clone(),
+            taker_asset: order.taker_asset.clone(),
+            making_amount: fill.making_amount,
+            taking_amount: fill.taking_amount,
+            remaining: fill.remaining - fill.making_amount,
+            mode: prepared.mode,
+            allowed_sender: order.allowed_sender.clone(),
+            integrator_fee,
+            protocol_fee,
+        };
+        events::order_filled(env, order, &fill.order_hash, event);
     }
 
     /// Fill order with args (equivalent to EVM fillOrderArgs) - RESOLVER COMPATIBILITY
+    ///
+    /// When `_ARGS_HAS_TARGET` is set, `args` starts with the target that receives the
+    /// making amount (the pre-computed src escrow), encoded as a 4-byte big-endian length
+    /// followed by the target's address XDR. After the extension and taker interaction
+    /// (see `decode_args`), the rest of `args` goes to the factory. `taker` authorizes the
+    /// call and pays the taking amount directly, as the resolver does on EVM.
     pub fn fill_args(
         env: &Env,
         resolver_order: ResolverOrder,
         signature: Bytes,
+        taker: Address,      // Resolver paying the taking amount
         amount: u128,
         taker_traits: I256,  // Resolver uses I256
         args: Bytes,         // Cross-chain args
     ) -> Result<(), Error> {
+        taker.require_auth();
+
         // Convert ResolverOrder to LOP Order
-        let order = Self::convert_resolver_order(env, resolver_order.clone())?;
-        
-        // Extract taker from args (first 32 bytes if _ARGS_HAS_TARGET is set)
-        let taker = Self::extract_taker_from_args(env, &taker_traits, &args)?;
+        let order = Self::convert_resolver_order(env, resolver_order, &signature)?;
         
         // Convert I256 taker_traits to TakerTraits struct
         let taker_traits_struct = Self::convert_taker_traits(env, &taker_traits)?;
         
-        // Call the existing fill_order function
-        let (making_amount, taking_amount, order_hash) = Self::fill_order(env, order.clone(), signature.clone(), taker.clone(), amount, taker_traits_struct)?;
+        // Decode the target (length-prefixed address if _ARGS_HAS_TARGET is set) and the taker interaction
+        let args = Self::decode_args(env, &taker_traits_struct, &args, &taker)?;
         
-        // Process cross-chain args if needed
-        Self::process_cross_chain_args(env, &args)?;
+        // Fill the order, sending the making amount to the target. Resolver orders carry no
+        // extension, so their post-interaction goes to the factory with the rest of `args`.
+        Self::fill(env, order, signature, &taker, &args, amount, taker_traits_struct)?;
         
-        // INTEGRATION: Call factory post_interaction after successful order execution
-        Self::call_factory_post_interaction(env, &resolver_order, &order_hash, &taker, making_amount, taking_amount, &args)?;
+        // Process cross-chain args if needed
+        Self::process_cross_chain_args(env, &args.extra)?;
         
         Ok(())
     }
 
-    /// Cancel an order
-    pub fn cancel_order(env: &Env, maker: Address, order_hash: BytesN<32>) -> Result<(), Error> {
+    /// Pre-register an order on-chain (equivalent to EVM ERC-1271 contract orders).
+    /// Registered orders are filled with an empty signature and no further maker auth: the
+    /// maker leg spends the allowance the maker approved to this contract.
+    pub fn register_order(env: &Env, order: Order) -> Result<BytesN<32>, Error> {
+        Self::validate_order(&order)?;
+        order.maker.require_auth();
+
+        let order_hash = Self::hash_order(env, order);
+        Self::set_persistent(env, &DataKey::RegisteredOrder(order_hash.clone()), &true);
+
+        env.events().publish(("OrderRegistered",), order_hash.clone());
+
+        Ok(order_hash)
+    }
+
+    /// Check whether an order was pre-registered by its maker
+    pub fn is_order_registered(env: &Env, order_hash: BytesN<32>) -> bool {
+        env.storage().persistent().has(&DataKey::RegisteredOrder(order_hash))
+    }
+
+    /// Post an order to the on-chain order book, listed until `expiration_ledger`
+    ///
+    /// Signed orders are posted with their signature, which takers pass on to `fill_order`.
+    /// With an empty signature the maker approves the order through Soroban auth: it is
+    /// registered as by `register_order` and fills need no signature. `HAS_EXTENSION` orders
+    /// are posted with the extension takers pass in args.
+    pub fn post_order(env: &Env, order: Order, extension: Bytes, signature: Bytes, expiration_ledger: u32) -> Result<BytesN<32>, Error> {
+        Self::validate_order(&order)?;
+        let attached = extension::attach(order.clone(), &extension)?;
+        let order_hash = Self::hash_order(env, attached.clone());
+        if signature.is_empty() {
+            order.maker.require_auth();
+            Self::set_persistent(env, &DataKey::RegisteredOrder(order_hash.clone()), &true);
+        } else if signature.len() == Self::EVM_SIGNATURE_LEN {
+            Self::verify_evm_signature(env, &attached, &order_hash, &signature)?;
+        } else {
+            Self::verify_signature(env, &attached, &order_hash, &signature)?;
+        }
+        if let (OrderStatus::Cancelled | OrderStatus::FullyFilled, _) = Self::order_status(env, attached) {
+            return Err(Error::InvalidatedOrder);
+        }
+
+        order_book::post(env, &order_hash, &PostedOrder { order, extension, signature, expiration_ledger })?;
+        env.events().publish(("OrderPosted",), (order_hash.clone(), expiration_ledger));
+
+        Ok(order_hash)
+    }
+
+    /// A posted order, until its expiration ledger passes
+    pub fn posted_order(env: &Env, order_hash: BytesN<32>) -> Option<PostedOrder> {
+        order_book::get(env, &order_hash)
+    }
+
+    /// Active posted orders selling `maker_asset` for `taker_asset`, from index slot `cursor`
+    pub fn orders_by_pair(env: &Env, maker_asset: Address, taker_asset: Address, cursor: u32, limit: u32) -> OrderPage {
+        order_book::page(env, OrderIndex::Pair(maker_asset, taker_asset), cursor, limit)
+    }
+
+    /// Active posted orders of `maker`, from index slot `cursor`
+    pub fn orders_by_maker(env: &Env, maker: Address, cursor: u32, limit: u32) -> OrderPage {
+        order_book::page(env, OrderIndex::Maker(maker), cursor, limit)
+    }
+
+    /// Bind an EVM signer to a Stellar funding account. Orders naming `maker` and carrying
+    /// a secp256k1 signature from `evm_signer` can then be filled against the maker's funds.
+    pub fn bind_evm_signer(env: &Env, maker: Address, evm_signer: BytesN<20>) -> Result<(), Error> {
         maker.require_auth();
+
+        Self::set_persistent(env, &DataKey::EvmSigner(maker.clone()), &evm_signer);
+
+        env.events().publish(("EvmSignerBound",), (maker, evm_signer));
+
+        Ok(())
+    }
+
+    /// Get the EVM signer bound to a Stellar funding account
+    pub fn evm_signer_for_maker(env: &Env, maker: Address) -> Option<BytesN<20>> {
+        env.storage().persistent().get(&DataKey::EvmSigner(maker))
+    }
+
+    /// Cancel an order. Only the order's maker can cancel it. The order is marked cancelled,
+    /// and orders using the bit invalidator also have their nonce invalidated.
+    pub fn cancel_order(env: &Env, order: Order) -> Result<(), Error> {
+        order.maker.require_auth();
+
+        let order_hash = Self::hash_order(env, order.clone());
+        let (_, remaining) = Self::order_status(env, order.clone());
+        let traits = MakerTraits(order.maker_traits);
+        if traits.use_bit_invalidator() {
+            Self::invalidate_nonce(env, &order.maker, traits.nonce_or_epoch());
+        }
+        Self::update_remaining_amount(env, &order_hash, RemainingInvalidator::Cancelled);
         
-        let mut remaining_inv: Map<BytesN<32>, u128> = env.storage().instance().get(&Self::REMAINING_INVALIDATOR).unwrap_or(Map::new(env));
-        remaining_inv.set(order_hash.clone(), 0);
-        env.storage().instance().set(&Self::REMAINING_INVALIDATOR, &remaining_inv);
-        
-        env.events().publish(("OrderCancelled",), order_hash);
+        events::order_cancelled(env, &order, &order_hash, remaining);
         
         Ok(())
     }
 
-    /// Get remaining amount for an order
+    /// Invalidate any of the maker's nonces in `slot` whose bit is set in `bits`
+    /// (nonce = slot * 256 + bit index)
+    pub fn bits_invalidate_for_order(env: &Env, maker: Address, slot: u32, bits: U256) -> Result<(), Error> {
+        maker.require_auth();
+
+        let current = Word256::from_u256(&Self::bit_invalidator_for_order(env, maker.clone(), slot));
+        let updated = current.or(&Word256::from_u256(&bits)).to_u256(env);
+        Self::set_persistent(env, &DataKey::BitInvalidator(maker.clone(), slot), &updated);
+
+        env.events().publish(("BitInvalidatorUpdated",), (maker, slot, updated));
+
+        Ok(())
+    }
+
+    /// Get the maker's bit invalidator word for a nonce slot
+    pub fn bit_invalidator_for_order(env: &Env, maker: Address, slot: u32) -> U256 {
+        env.storage().persistent().get(&DataKey::BitInvalidator(maker, slot)).unwrap_or(U256::from_u32(env, 0))
+    }
+
+    /// Advance the maker's epoch for `series`, cancelling every order of that series
+    /// signed for an earlier epoch
+    pub fn increase_epoch(env: &Env, maker: Address, series: u64) -> Result<(), Error> {
+        maker.require_auth();
+
+        let epoch = Self::epoch(env, maker.clone(), series) + 1;
+        Self::set_persistent(env, &DataKey::Epoch(maker.clone(), series), &epoch);
+
+        env.events().publish(("EpochIncreased",), (maker, series, epoch));
+
+        Ok(())
+    }
+
+    /// Get the maker's current epoch for a series
+    pub fn epoch(env: &Env, maker: Address, series: u64) -> u64 {
+        env.storage().persistent().get(&DataKey::Epoch(maker, series)).unwrap_or(0)
+    }
+
+    /// Get the EVM-style remaining invalidator word for an order: 0 for orders never filled,
+    /// `!remaining` after fills, `u128::MAX` once fully filled or cancelled.
+    /// Use `order_status` to tell cancelled orders apart.
     pub fn remaining_invalidator_for_order(env: &Env, _maker: Address, order_hash: BytesN<32>) -> u128 {
-        let remaining_inv: Map<BytesN<32>, u128> = env.storage().instance().get(&Self::REMAINING_INVALIDATOR).unwrap_or(Map::new(env));
-        remaining_inv.get(order_hash).unwrap_or(0)
+        Self::remaining_invalidator(env, &order_hash).map_or(0, |invalidator| invalidator.word())
     }
 
-    /// Hash an order (EXACTLY matches EVM implementation)
+    /// The `allowed_sender` of public orders, which any taker may fill. As `receiver`, the
+    /// same zero address pays the taking amount to the maker.
+    pub fn any_sender(env: &Env) -> Address {
+        Self::zero_address(env)
+    }
+
+    /// Fill status of an order and its remaining making amount. A single-fill order is done once
+    /// its nonce is used: fully filled if it was filled, cancelled otherwise.
+    pub fn order_status(env: &Env, order: Order) -> (OrderStatus, u128) {
+        let order_hash = Self::hash_order(env, order.clone());
+        let traits = MakerTraits(order.maker_traits);
+        let remaining = Self::remaining_invalidator(env, &order_hash).map(|invalidator| invalidator.remaining());
+        if traits.use_bit_invalidator() && Self::is_nonce_invalidated(env, &order.maker, traits.nonce_or_epoch()) {
+            return match remaining {
+                Some(Some(_)) => (OrderStatus::FullyFilled, 0),
+                _ => (OrderStatus::Cancelled, 0),
+            };
+        }
+        match remaining {
+            None => (OrderStatus::Unfilled, order.making_amount),
+            Some(None) => (OrderStatus::Cancelled, 0),
+            Some(Some(0)) => (OrderStatus::FullyFilled, 0),
+            Some(Some(remaining)) => (OrderStatus::PartiallyFilled, remaining),
+        }
+    }
+
+    /// Taking amount required for `making_amount` of the order at `timestamp`, following the
+    /// order's Dutch auction if it has one (see auction.rs), with the fees of a resolver off
+    /// the order's whitelist. `extension` is attached to `HAS_EXTENSION` orders as in fills.
+    pub fn quote_at(env: &Env, order: Order, extension: Bytes, making_amount: u128, timestamp: u64) -> Result<u128, Error> {
+        Self::validate_order(&order)?;
+        let order = extension::attach(order, &extension)?;
+        let fee_bps = Self::order_fees(env, &order, None)?.as_ref().map_or(0, Fees::total);
+        Self::get_taking_amount(env, &order, making_amount, timestamp, fee_bps)
+    }
+
+    /// Order that consumed a one-cancels-other group, once one did
+    pub fn group_consumer(env: &Env, maker: Address, group_id: BytesN<32>) -> Option<BytesN<32>> {
+        env.storage().persistent().get(&DataKey::GroupConsumer(maker, group_id))
+    }
+
+    /// Hash an order: EIP-712 digest `keccak256(0x1901 || domainSeparator || structHash)`.
+    /// `HAS_EXTENSION` orders are hashed with their extension attached (see extension.rs).
     pub fn hash_order(env: &Env, order: Order) -> BytesN<32> {
-        // Step 1: Hash the interactions (dynamic data)
-        let interactions_hash = env.crypto().keccak256(&order.interactions);
-        
-        // Step 2: Serialize all fields into a Bytes buffer
+        let mut buf = Bytes::from_array(env, &[0x19, 0x01]);
+        buf.append(&Bytes::from_array(env, &Self::domain_separator(env).to_array()));
+        buf.append(&Bytes::from_array(env, &Self::order_struct_hash(env, &order).to_array()));
+        env.crypto().keccak256(&buf)
+    }
+
+    /// EIP-712 domain separator, bound to the network and to this LOP deployment through
+    /// `salt = keccak256(network id || contract id)`, so signed orders cannot replay elsewhere.
+    /// There is no chainId: EVM wallets refuse to sign for a chainId other than their own.
+    pub fn domain_separator(env: &Env) -> BytesN<32> {
+        let mut salt = Bytes::from_array(env, &env.ledger().network_id().to_array());
+        salt.append(&Self::address_to_bytes(env, &env.current_contract_address()));
         let mut buf = Bytes::new(env);
-        buf.append(&Bytes::from_slice(env, Self::ORDER_TYPE_HASH));
+        buf.append(&Self::keccak(env, &Bytes::from_slice(env, Self::DOMAIN_TYPE)));
+        buf.append(&Self::keccak(env, &Bytes::from_slice(env, Self::DOMAIN_NAME)));
+        buf.append(&Self::keccak(env, &Bytes::from_slice(env, Self::DOMAIN_VERSION)));
+        buf.append(&Self::keccak(env, &salt));
+        env.crypto().keccak256(&buf)
+    }
+
+    // Helper functions
+
+    /// EIP-712 `hashStruct(order)`: every field encoded as a 32-byte word
+    fn order_struct_hash(env: &Env, order: &Order) -> BytesN<32> {
+        let mut buf = Bytes::new(env);
+        buf.append(&Self::keccak(env, &Bytes::from_slice(env, Self::ORDER_TYPE)));
+        buf.append(&Self::uint256(env, order.salt as u128));
         buf.append(&Self::address_to_bytes(env, &order.maker_asset));
         buf.append(&Self::address_to_bytes(env, &order.taker_asset));
         buf.append(&Self::address_to_bytes(env, &order.maker));
         buf.append(&Self::address_to_bytes(env, &order.receiver));
         buf.append(&Self::address_to_bytes(env, &order.allowed_sender));
-        buf.append(&Bytes::from_slice(env, &order.salt.to_be_bytes()));
-        buf.append(&Bytes::from_slice(env, &order.making_amount.to_be_bytes()));
-        buf.append(&Bytes::from_slice(env, &order.taking_amount.to_be_bytes()));
-        buf.append(&Bytes::from_slice(env, &order.offsets.to_be_bytes()));
-        buf.append(&Bytes::from_array(env, &interactions_hash.to_array()));
+        buf.append(&Self::uint256(env, order.making_amount));
+        buf.append(&Self::uint256(env, order.taking_amount));
+        buf.append(&Self::uint256(env, order.maker_traits));
+        if MakerTraits(order.maker_traits).has_extension() {
+            // The whole extension stands in for the interactions (see extension.rs)
+            buf.append(&Self::uint256(env, 0));
+            buf.append(&Self::keccak(env, &extension::encode(env, order)));
+        } else {
+            buf.append(&Self::uint256(env, order.offsets));
+            buf.append(&Self::keccak(env, &order.interactions));
+        }
+        let mut evm_signer = [0u8; 32];
+        evm_signer[12..].copy_from_slice(&order.evm_signer.to_array());
+        buf.append(&Bytes::from_array(env, &evm_signer));
         env.crypto().keccak256(&buf)
     }
 
-    // Helper functions
+    fn keccak(env: &Env, data: &Bytes) -> Bytes {
+        Bytes::from_array(env, &env.crypto().keccak256(data).to_array())
+    }
+
+    /// Big-endian uint256 word
+    fn uint256(env: &Env, value: u128) -> Bytes {
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        Bytes::from_array(env, &word)
+    }
+
     fn validate_order(order: &Order) -> Result<(), Error> {
         if order.making_amount == 0 || order.taking_amount == 0 {
             return Err(Error::SwapWithZeroAmount);
         }
+        extension::check_offsets(order.offsets, order.interactions.len())?;
         Ok(())
     }
 
-    fn verify_signature(env: &Env, order: &Order, signature: &Bytes) -> Result<(), Error> {
-        let order_hash = Self::hash_order(env, order.clone());
+    /// Enforce the order's allowed sender and the maker's MakerTraits for a fill by `taker`.
+    ///
+    /// Private orders can only be filled by their allowed sender, who authorized the fill like
+    /// any taker. The allowed-sender bits of the traits only filter (see maker_traits.rs).
+    /// Orders that disallow partial or multiple fills are invalidated by their nonce; series
+    /// orders checked by the epoch manager only fill at the maker's current epoch.
+    fn check_maker_traits(env: &Env, order: &Order, taker: &Address) -> Result<(), Error> {
+        if order.allowed_sender != Self::zero_address(env) && *taker != order.allowed_sender {
+            return Err(Error::PrivateOrder);
+        }
+        let traits = MakerTraits(order.maker_traits);
+        let mut sender = [0u8; 32];
+        Self::address_to_bytes(env, taker).copy_into_slice(&mut sender);
+        if !traits.is_allowed_sender(&sender) {
+            return Err(Error::PrivateOrder);
+        }
+        if traits.is_expired(env.ledger().timestamp()) {
+            return Err(Error::OrderExpired);
+        }
+        if traits.need_check_epoch_manager() {
+            if traits.use_bit_invalidator() {
+                return Err(Error::EpochManagerAndBitInvalidatorsAreIncompatible);
+            }
+            if Self::epoch(env, order.maker.clone(), traits.series()) != traits.nonce_or_epoch() {
+                return Err(Error::WrongSeriesNonce);
+            }
+        }
+        if traits.use_bit_invalidator() && Self::is_nonce_invalidated(env, &order.maker, traits.nonce_or_epoch()) {
+            return Err(Error::BitInvalidatedOrder);
+        }
+        Ok(())
+    }
+
+    /// Bit invalidator slot and bit index of a nonce
+    fn nonce_position(nonce: u64) -> (u32, u32) {
+        ((nonce >> 8) as u32, (nonce & 0xff) as u32)
+    }
+
+    fn is_nonce_invalidated(env: &Env, maker: &Address, nonce: u64) -> bool {
+        let (slot, bit) = Self::nonce_position(nonce);
+        Word256::from_u256(&Self::bit_invalidator_for_order(env, maker.clone(), slot)).bit(bit)
+    }
+
+    fn invalidate_nonce(env: &Env, maker: &Address, nonce: u64) {
+        let (slot, bit) = Self::nonce_position(nonce);
+        let mut word = Word256::from_u256(&Self::bit_invalidator_for_order(env, maker.clone(), slot));
+        word.set_bit(bit);
+        Self::set_persistent(env, &DataKey::BitInvalidator(maker.clone(), slot), &word.to_u256(env));
+    }
+
+    /// Making amount for `taking_amount` at `timestamp`, bumped by the order's auction if it
+    /// has one. `taking_amount` includes `fee_bps` of fees.
+    fn get_making_amount(env: &Env, order: &Order, taking_amount: u128, timestamp: u64, fee_bps: u128) -> Result<u128, Error> {
+        let details = extension::field(order, DynamicField::MakingAmountData)?;
+        let rate_bump = if details.is_empty() { 0 } else { auction::rate_bump(&details, timestamp)? };
+        amount_calculator::making_amount(env, order, fee::without_fee(env, taking_amount, fee_bps), rate_bump)
+    }
+
+    /// Taking amount for `making_amount` at `timestamp`, bumped by the order's auction if it
+    /// has one, plus `fee_bps` of fees
+    fn get_taking_amount(env: &Env, order: &Order, making_amount: u128, timestamp: u64, fee_bps: u128) -> Result<u128, Error> {
+        let details = extension::field(order, DynamicField::TakingAmountData)?;
+        let rate_bump = if details.is_empty() { 0 } else { auction::rate_bump(&details, timestamp)? };
+        fee::with_fee(env, amount_calculator::taking_amount(env, order, making_amount, rate_bump)?, fee_bps)
+    }
+
+    /// The order's fees for a fill by `taker` (see fee.rs), if it charges any
+    fn order_fees(env: &Env, order: &Order, taker: Option<&Address>) -> Result<Option<Fees>, Error> {
+        let details = extension::field(order, DynamicField::FeeData)?;
+        if details.is_empty() {
+            return Ok(None);
+        }
+        Fees::parse(env, &details, taker).map(Some)
+    }
+
+    /// Making amount open to this fill: `remaining`, limited by the order's TWAP if it has one
+    fn available_amount(env: &Env, order: &Order, remaining: u128) -> Result<u128, Error> {
+        let details = extension::field(order, DynamicField::TwapData)?;
+        if details.is_empty() {
+            return Ok(remaining);
+        }
+        twap::available(&details, order.making_amount, remaining, env.ledger().timestamp())
+    }
+
+    /// Evaluate the order's predicate, if it has one
+    fn check_predicate(env: &Env, order: &Order) -> Result<(), Error> {
+        let predicate = extension::field(order, DynamicField::Predicate)?;
+        if !predicate.is_empty() && !predicate::evaluate(env, order, &predicate)? {
+            return Err(Error::PredicateIsNotTrue);
+        }
+        Ok(())
+    }
+
+    /// Reject fills of an order whose group was consumed by a sibling
+    fn check_group(env: &Env, group: &OrderGroup, order_hash: &BytesN<32>) -> Result<(), Error> {
+        match Self::group_consumer(env, group.maker.clone(), group.id.clone()) {
+            Some(consumer) if consumer != *order_hash => Err(Error::GroupConsumed),
+            _ => Ok(()),
+        }
+    }
+
+    /// Mark the group consumed by `order_hash` if this fill consumes it
+    fn consume_group(env: &Env, group: &OrderGroup, order_hash: &BytesN<32>, remaining: u128) {
+        if group.consumed_by(remaining) && Self::group_consumer(env, group.maker.clone(), group.id.clone()).is_none() {
+            Self::set_persistent(env, &DataKey::GroupConsumer(group.maker.clone(), group.id.clone()), order_hash);
+            env.events().publish(("GroupConsumed",), (group.maker.clone(), group.id.clone(), order_hash.clone()));
+        }
+    }
+
+    /// Making and taking amounts of a fill (EVM `_fill`). In making mode `amount` is the
+    /// making amount, capped at `remaining`; otherwise it is the taking amount, and a fill
+    /// past `remaining` is reduced to it as long as that doesn't cost more than `amount`.
+    /// Making amounts round down and taking amounts round up, in the maker's favor.
+    fn fill_amounts(env: &Env, order: &Order, amount: u128, taker_traits: &TakerTraits, remaining: u128, fee_bps: u128) -> Result<(u128, u128), Error> {
+        let now = env.ledger().timestamp();
+        if taker_traits.maker_amount {
+            let making_amount = amount.min(remaining);
+            let taking_amount = Self::get_taking_amount(env, order, making_amount, now, fee_bps)?;
+            Self::check_threshold(env, taker_traits, amount, making_amount, taking_amount)?;
+            return Ok((making_amount, taking_amount));
+        }
+        let mut making_amount = Self::get_making_amount(env, order, amount, now, fee_bps)?;
+        let mut taking_amount = amount;
+        if making_amount > remaining {
+            making_amount = remaining;
+            taking_amount = Self::get_taking_amount(env, order, making_amount, now, fee_bps)?;
+            if taking_amount > amount {
+                return Err(Error::TakingAmountExceeded);
+            }
+        }
+        Self::check_threshold(env, taker_traits, amount, making_amount, taking_amount)?;
+        Ok((making_amount, taking_amount))
+    }
+
+    /// Slippage protection: in making mode the threshold caps the taking amount for `amount`,
+    /// in taking mode it is the minimum making amount for `amount`. When the fill was reduced
+    /// to the remaining amount the threshold applies pro rata. A zero threshold disables the check.
+    fn check_threshold(env: &Env, taker_traits: &TakerTraits, amount: u128, making_amount: u128, taking_amount: u128) -> Result<(), Error> {
+        let threshold = taker_traits.threshold;
+        if threshold == 0 {
+            return Ok(());
+        }
+        let product = |a: u128, b: u128| U256::from_u128(env, a).mul(&U256::from_u128(env, b));
+        if taker_traits.maker_amount {
+            let too_high = if amount == making_amount {
+                taking_amount > threshold
+            } else {
+                product(taking_amount, amount) > product(threshold, making_amount)
+            };
+            if too_high {
+                return Err(Error::TakingAmountTooHigh);
+            }
+        } else {
+            let too_low = if amount == taking_amount {
+                making_amount < threshold
+            } else {
+                product(making_amount, amount) < product(threshold, taking_amount)
+            };
+            if too_low {
+                return Err(Error::MakingAmountTooLow);
+            }
+        }
+        Ok(())
+    }
+
+    /// Authorize a fill on behalf of the maker.
+    ///
+    /// A 65-byte `r || s || v` signature is an EVM secp256k1 signature (see
+    /// `verify_evm_signature`); any other non-empty signature is verified as an ed25519
+    /// signature by the maker's account key. An empty signature selects Soroban auth, which
+    /// also works for contract makers (smart wallets, passkeys, multisig): the order must
+    /// have been registered with `register_order`, or the maker must authorize this call
+    /// for `(order_hash, amount)`, unless `authorize` is unset (quotes). Signed and registered
+    /// orders need no maker auth at fill time, since the maker leg spends an allowance.
+    fn authorize_order(env: &Env, order: &Order, order_hash: &BytesN<32>, signature: &Bytes, amount: u128, authorize: bool) -> Result<(), Error> {
+        if signature.len() == Self::EVM_SIGNATURE_LEN {
+            return Self::verify_evm_signature(env, order, order_hash, signature);
+        }
+        if !signature.is_empty() {
+            return Self::verify_signature(env, order, order_hash, signature);
+        }
+        if !authorize || Self::is_order_registered(env, order_hash.clone()) {
+            return Ok(());
+        }
+        order.maker.require_auth_for_args(vec![env, order_hash.into_val(env), amount.into_val(env)]);
+        Ok(())
+    }
+
+    /// Verify the maker's ed25519 signature over the order hash.
+    ///
+    /// Malformed input (wrong signature length, contract-address maker) is reported as
+    /// `BadSignature`. A well-formed signature that does not verify traps inside the host
+    /// (`ed25519_verify` panics), so the whole invocation fails and nothing is written.
+    fn verify_signature(env: &Env, order: &Order, order_hash: &BytesN<32>, signature: &Bytes) -> Result<(), Error> {
         let maker_pubkey = Self::address_to_public_key(env, &order.maker)?;
         if signature.len() != 64 {
             return Err(Error::BadSignature);
         }
         let mut sig_bytes = [0u8; 64];
-        for i in 0..64 {
-            sig_bytes[i] = signature.get(i as u32).unwrap_or(0);
-        }
+        signature.copy_into_slice(&mut sig_bytes);
         let signature_bytes = BytesN::from_array(env, &sig_bytes);
         let order_hash_bytes = Bytes::from_array(env, &order_hash.to_array());
         env.crypto().ed25519_verify(&maker_pubkey, &order_hash_bytes, &signature_bytes);
         Ok(())
     }
 
-    fn get_remaining_amount(env: &Env, order_hash: &BytesN<32>) -> u128 {
-        let remaining_inv: Map<BytesN<32>, u128> = env.storage().instance().get(&Self::REMAINING_INVALIDATOR).unwrap_or(Map::new(env));
-        remaining_inv.get(order_hash.clone()).unwrap_or(u128::MAX)
+    /// Write a persistent entry and extend its TTL
+    fn set_persistent<V: IntoVal<Env, soroban_sdk::Val>>(env: &Env, key: &DataKey, value: &V) {
+        env.storage().persistent().set(key, value);
+        env.storage().persistent().extend_ttl(key, Self::PERSISTENT_TTL_THRESHOLD, Self::PERSISTENT_TTL_EXTEND_TO);
+    }
+
+    fn remaining_invalidator(env: &Env, order_hash: &BytesN<32>) -> Option<RemainingInvalidator> {
+        env.storage().persistent().get(&DataKey::RemainingInvalidator(order_hash.clone()))
+    }
+
+    /// Remaining making amount: the order's full making amount until its first fill
+    fn get_remaining_amount(env: &Env, order: &Order, order_hash: &BytesN<32>) -> Result<u128, Error> {
+        match Self::remaining_invalidator(env, order_hash) {
+            None => Ok(order.making_amount),
+            Some(invalidator) => invalidator.remaining().ok_or(Error::InvalidatedOrder),
+        }
+    }
+
+    fn update_remaining_amount(env: &Env, order_hash: &BytesN<32>, invalidator: RemainingInvalidator) {
+        Self::set_persistent(env, &DataKey::RemainingInvalidator(order_hash.clone()), &invalidator);
     }
 
-    fn update_remaining_amount(env: &Env, order_hash: &BytesN<32>, remaining: u128) {
-        let mut remaining_inv: Map<BytesN<32>, u128> = env.storage().instance().get(&Self::REMAINING_INVALIDATOR).unwrap_or(Map::new(env));
-        remaining_inv.set(order_hash.clone(), remaining);
-        env.storage().instance().set(&Self::REMAINING_INVALIDATOR, &remaining_inv);
+    fn zero_address(env: &Env) -> Address {
+        Address::from_string(&String::from_str(env, Self::ZERO_ADDRESS))
     }
 
-    fn transfer_assets(env: &Env, order: &Order, taker: &Address, making_amount: u128, taking_amount: u128) -> Result<(), Error> {
-        let escrow_address = env.current_contract_address();
+    /// Maker leg: the making amount goes from the maker straight to `target`, pulled against
+    /// the allowance the maker approved to this contract (EVM `transferFrom`), so signed and
+    /// registered orders fill without the maker authorizing the fill
+    fn transfer_maker_asset(env: &Env, order: &Order, target: &Address, making_amount: u128) {
         let maker_token = token::Client::new(env, &order.maker_asset);
+        maker_token.transfer_from(&env.current_contract_address(), &order.maker, target, &(making_amount as i128));
+    }
+
+    /// Taker leg: the taking amount goes from the taker straight to the order's receiver (or
+    /// the maker when no receiver is set) and the fee recipients
+    fn transfer_taker_asset(env: &Env, order: &Order, taker: &Address, payouts: &Vec<(Address, u128)>) {
         let taker_token = token::Client::new(env, &order.taker_asset);
-        maker_token.transfer(&order.maker, &escrow_address, &(making_amount as i128));
-        taker_token.transfer(taker, &order.maker, &(taking_amount as i128));
-        maker_token.transfer(&escrow_address, taker, &(making_amount as i128));
-        Ok(())
+        for (recipient, amount) in payouts.iter().filter(|(_, amount)| *amount > 0) {
+            taker_token.transfer(taker, &recipient, &(amount as i128));
+        }
     }
 
+    /// Recipient of the taking amount: the order's receiver, or the maker when none is set
+    fn receiver(env: &Env, order: &Order) -> Address {
+        if order.receiver == Self::zero_address(env) { order.maker.clone() } else { order.receiver.clone() }
+    }
+
+    /// The 32 bytes behind an address strkey: the ed25519 key of an account (G...) or the
+    /// contract id of a contract (C...), taken from the tail of the address XDR
     fn address_to_bytes(env: &Env, address: &Address) -> Bytes {
-        // Use address.to_xdr(env) to get Bytes, then take the first 32 bytes
         let xdr = address.to_xdr(env);
-        let mut arr = [0u8; 32];
-        for i in 0..32 {
-            arr[i] = xdr.get(i as u32).unwrap_or(0);
+        xdr.slice(xdr.len() - 32..)
+    }
+
+    /// Verify an EVM maker's secp256k1 signature over the order hash.
+    ///
+    /// The signer is recovered from `r || s || v` (v = 27/28 or 0/1) and its Ethereum
+    /// address must equal `order.evm_signer` (non-zero), which in turn must be bound to `order.maker`
+    /// via `bind_evm_signer`. A signature that cannot be recovered traps inside the host.
+    fn verify_evm_signature(env: &Env, order: &Order, order_hash: &BytesN<32>, signature: &Bytes) -> Result<(), Error> {
+        let evm_signer = order.evm_signer.clone();
+        if evm_signer.to_array() == [0u8; 20] {
+            return Err(Error::BadSignature);
+        }
+        if Self::evm_signer_for_maker(env, order.maker.clone()) != Some(evm_signer.clone()) {
+            return Err(Error::SignerNotBound);
         }
-        Bytes::from_array(env, &arr)
+
+        let mut sig_bytes = [0u8; 64];
+        signature.slice(0..64).copy_into_slice(&mut sig_bytes);
+        let recovery_id = match signature.get(64).unwrap_or(0) {
+            v @ (0 | 1) => v as u32,
+            v @ (27 | 28) => (v - 27) as u32,
+            _ => return Err(Error::BadSignature),
+        };
+        let public_key = env.crypto().secp256k1_recover(order_hash, &BytesN::from_array(env, &sig_bytes), recovery_id);
+
+        // Ethereum address: last 20 bytes of keccak256 over the uncompressed key without its 0x04 prefix
+        let key_hash = env.crypto().keccak256(&Bytes::from_slice(env, &public_key.to_array()[1..]));
+        let mut recovered = [0u8; 20];
+        recovered.copy_from_slice(&key_hash.to_array()[12..]);
+        if recovered != evm_signer.to_array() {
+            return Err(Error::BadSignature);
+        }
+        Ok(())
     }
 
+    /// Extract the ed25519 public key of a Stellar account (G...) address.
+    ///
+    /// `to_xdr` yields `ScVal::Address(ScAddress::Account(PublicKey::Ed25519(key)))`:
+    /// a 4-byte ScVal tag, a 4-byte ScAddress tag, a 4-byte PublicKey tag, then the
+    /// 32-byte key. Contract (C...) addresses cannot sign and are rejected.
     fn address_to_public_key(env: &Env, address: &Address) -> Result<BytesN<32>, Error> {
         let xdr = address.to_xdr(env);
-        let mut arr = [0u8; 32];
-        for i in 0..32 {
-            arr[i] = xdr.get(i as u32).unwrap_or(0);
+        if xdr.len() != Self::ACCOUNT_XDR_LEN {
+            return Err(Error::BadSignature);
         }
+        let mut header = [0u8; 12];
+        xdr.slice(0..12).copy_into_slice(&mut header);
+        if header != Self::ACCOUNT_XDR_HEADER {
+            return Err(Error::BadSignature);
+        }
+        let mut arr = [0u8; 32];
+        xdr.slice(12..Self::ACCOUNT_XDR_LEN).copy_into_slice(&mut arr);
         Ok(BytesN::from_array(env, &arr))
     }
 
     // RESOLVER COMPATIBILITY FUNCTIONS
 
     /// Convert ResolverOrder to LOP Order
-    fn convert_resolver_order(env: &Env, resolver_order: ResolverOrder) -> Result<Order, Error> {
+    fn convert_resolver_order(env: &Env, resolver_order: ResolverOrder, signature: &Bytes) -> Result<Order, Error> {
         // Create a default LOP Order from ResolverOrder
         // Set missing fields to defaults
         let maker = resolver_order.maker.clone();
@@ -269,43 +1085,67 @@ impl StellarLimitOrderProtocol {
             taker_asset: resolver_order.taker_asset,
             maker: maker.clone(),
             receiver: resolver_order.receiver,
-            allowed_sender: maker, // Use maker as allowed_sender (public order)
+            allowed_sender: Self::zero_address(env), // Resolver orders are public
             making_amount: resolver_order.making_amount,
             taking_amount: resolver_order.taking_amount,
+            maker_traits: resolver_order.maker_traits,
             offsets: 0, // Default offsets
             interactions: Bytes::new(env), // Empty interactions
+            evm_signer: Self::resolver_order_signer(env, maker, signature), // EVM makers sign through their bound key
         };
         Ok(order)
     }
 
-    /// Extract taker address from args if _ARGS_HAS_TARGET is set
-    fn extract_taker_from_args(env: &Env, taker_traits: &I256, args: &Bytes) -> Result<Address, Error> {
-        // Check if bit 251 is set (_ARGS_HAS_TARGET flag)
-        // For now, we'll use a simplified approach since I256 doesn't have to_u256()
-        // In a real implementation, you'd need proper bit manipulation
-        let args_has_target = true; // Assume target is always present for cross-chain
-        
-        if args_has_target && args.len() >= 32 {
-            // For now, just use the current contract as taker
-            // In a real implementation, you'd extract and validate the target address
-            Ok(env.current_contract_address())
+    /// `evm_signer` of a resolver order: the maker's bound EVM key for EVM signatures, zero
+    /// otherwise, so binding a key doesn't change the hash of the maker's Stellar-signed orders
+    fn resolver_order_signer(env: &Env, maker: Address, signature: &Bytes) -> BytesN<20> {
+        let zero = BytesN::from_array(env, &[0u8; 20]);
+        if signature.len() != Self::EVM_SIGNATURE_LEN {
+            return zero;
+        }
+        Self::evm_signer_for_maker(env, maker).unwrap_or(zero)
+    }
+
+    /// Decode fill `args`: the target if _ARGS_HAS_TARGET is set (defaulting to `taker`), then
+    /// `args_extension_length` bytes of extension and `args_interaction_length` bytes of
+    /// taker interaction. Whatever follows is extra data for the factory.
+    fn decode_args(env: &Env, taker_traits: &TakerTraits, args: &Bytes, taker: &Address) -> Result<FillArgs, Error> {
+        let (target, args) = if taker_traits.args_has_target {
+            Self::split_address(env, args).ok_or(Error::InvalidArgs)?
         } else {
-            // Use current contract as taker (default behavior)
-            Ok(env.current_contract_address())
+            (taker.clone(), args.clone())
+        };
+        let extension_end = taker_traits.args_extension_length;
+        let interaction_end = extension_end
+            .checked_add(taker_traits.args_interaction_length)
+            .filter(|end| *end <= args.len())
+            .ok_or(Error::InvalidArgs)?;
+        Ok(FillArgs {
+            target,
+            extension: args.slice(..extension_end),
+            interaction: args.slice(extension_end..interaction_end),
+            extra: args.slice(interaction_end..),
+        })
+    }
+
+    /// Split a leading address, encoded as a 4-byte big-endian length followed by its XDR,
+    /// off `data`. Returns the address and the bytes after it.
+    fn split_address(env: &Env, data: &Bytes) -> Option<(Address, Bytes)> {
+        if data.len() < Self::TARGET_LENGTH_PREFIX {
+            return None;
         }
+        let mut prefix = [0u8; 4];
+        data.slice(0..Self::TARGET_LENGTH_PREFIX).copy_into_slice(&mut prefix);
+        let end = Self::TARGET_LENGTH_PREFIX
+            .checked_add(u32::from_be_bytes(prefix))
+            .filter(|end| *end <= data.len())?;
+        let address = Address::from_xdr(env, &data.slice(Self::TARGET_LENGTH_PREFIX..end)).ok()?;
+        Some((address, data.slice(end..)))
     }
 
     /// Convert I256 taker_traits to TakerTraits struct
-    fn convert_taker_traits(_env: &Env, _taker_traits: &I256) -> Result<TakerTraits, Error> {
-        // For now, we'll use default values since I256 doesn't have to_u256()
-        // In a real implementation, you'd need proper bit manipulation
-        let threshold = 0u128; // Default threshold
-        let skip_maker_permit = false; // Default to not skip permit
-        
-        Ok(TakerTraits {
-            threshold,
-            skip_maker_permit,
-        })
+    fn convert_taker_traits(_env: &Env, taker_traits: &I256) -> Result<TakerTraits, Error> {
+        Ok(TakerTraits::from_i256(taker_traits))
     }
 
     /// Process cross-chain args for additional functionality
@@ -319,90 +1159,146 @@ impl StellarLimitOrderProtocol {
         Ok(())
     }
 
-    /// INTEGRATION: Call factory post_interaction after successful order execution
-    fn call_factory_post_interaction(
-        env: &Env,
-        resolver_order: &ResolverOrder,
-        order_hash: &BytesN<32>,
-        taker: &Address,
-        making_amount: u128,
-        taking_amount: u128,
-        args: &Bytes,
-    ) -> Result<(), Error> {
-        // Get factory address from storage
-        let factory: Address = env.storage().instance().get(&Self::FACTORY)
-            .ok_or(Error::InvalidArgs)?; // Factory not set
+    fn factory(env: &Env) -> Result<Address, Error> {
+        env.storage().instance().get(&Self::FACTORY).ok_or(Error::InvalidArgs) // Factory not set
+    }
 
-        // Convert ResolverOrder to Factory Order format
+    /// Call an interaction listener with the standard arguments
+    /// `(order, extension, order_hash, taker, making_amount, taking_amount, remaining_making_amount, extra_data)`.
+    /// Listeners get the order in the factory's form and its extension as bytes (see
+    /// extension.rs), wherever the order carried it. A failing listener fails the fill.
+    fn call_interaction(env: &Env, listener: &Address, function: &str, order: &Order, fill: &Fill, extra_data: Bytes) {
         let factory_order = FactoryOrder {
-            salt: resolver_order.salt,
-            maker: resolver_order.maker.clone(),
-            receiver: resolver_order.receiver.clone(),
-            maker_asset: resolver_order.maker_asset.clone(),
-            taker_asset: resolver_order.taker_asset.clone(),
-            making_amount: resolver_order.making_amount,
-            taking_amount: resolver_order.taking_amount,
-            maker_traits: resolver_order.maker_traits,
+            salt: order.salt,
+            maker: order.maker.clone(),
+            receiver: order.receiver.clone(),
+            maker_asset: order.maker_asset.clone(),
+            taker_asset: order.taker_asset.clone(),
+            making_amount: order.making_amount,
+            taking_amount: order.taking_amount,
+            maker_traits: order.maker_traits,
         };
-
-        // Call factory.post_interaction with proper parameters
+        let extension = extension::encode(env, order);
         let call_args = vec![
             env,
             factory_order.into_val(env),
-            Bytes::new(env).into_val(env), // extension (empty for now)
-            order_hash.clone().into_val(env),
-            taker.clone().into_val(env),
-            making_amount.into_val(env),
-            taking_amount.into_val(env),
-            taking_amount.into_val(env), // remaining_making_amount (same as taking_amount for full fill)
-            args.clone().into_val(env),
+            extension.into_val(env),
+            fill.order_hash.clone().into_val(env),
+            fill.taker.clone().into_val(env),
+            fill.making_amount.into_val(env),
+            fill.taking_amount.into_val(env),
+            fill.remaining.into_val(env),
+            extra_data.into_val(env),
         ];
-
-        let result: Result<soroban_sdk::Val, soroban_sdk::Error> = 
-            env.invoke_contract(&factory, &Symbol::new(env, "post_interaction"), call_args);
-
-        match result {
-            Ok(_) => Ok(()),
-            Err(_) => Err(Error::TransferFailed), // Use TransferFailed for factory call failure
-        }
+        env.invoke_contract::<()>(listener, &Symbol::new(env, function), call_args);
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum Error {
-    TakingAmountExceeded,
-    OrderExpired,
-    SwapWithZeroAmount,
-    BadSignature,
-    TransferFailed,
-    InvalidArgs,
-    ConversionFailed,
+/// Taker-supplied parts of a fill, decoded from its args
+struct FillArgs {
+    target: Address,     // Receives the making amount
+    extension: Bytes,    // Extension of HAS_EXTENSION orders (see extension.rs)
+    interaction: Bytes,  // Taker interaction: listener address and its data
+    extra: Bytes,        // Passed on to the factory
 }
 
-impl From<Error> for soroban_sdk::Error {
-    fn from(err: Error) -> Self {
-        match err {
-            Error::TakingAmountExceeded => soroban_sdk::Error::from_type_and_code(ScErrorType::Contract, ScErrorCode::InvalidInput),
-            Error::OrderExpired => soroban_sdk::Error::from_type_and_code(ScErrorType::Contract, ScErrorCode::InvalidInput),
-            Error::SwapWithZeroAmount => soroban_sdk::Error::from_type_and_code(ScErrorType::Contract, ScErrorCode::InvalidInput),
-            Error::BadSignature => soroban_sdk::Error::from_type_and_code(ScErrorType::Contract, ScErrorCode::InvalidInput),
-            Error::TransferFailed => soroban_sdk::Error::from_type_and_code(ScErrorType::Contract, ScErrorCode::InvalidInput),
-            Error::InvalidArgs => soroban_sdk::Error::from_type_and_code(ScErrorType::Contract, ScErrorCode::InvalidInput),
-            Error::ConversionFailed => soroban_sdk::Error::from_type_and_code(ScErrorType::Contract, ScErrorCode::InvalidInput),
+/// A validated fill with its resolved listeners, each with its extra data
+struct PreparedFill {
+    order: Order,
+    fill: Fill,
+    mode: FillMode,
+    group: Option<OrderGroup>,
+    payouts: Vec<(Address, u128)>,   // Recipients of the taking amount
+    fee_amounts: (u128, u128),       // Integrator and protocol fees
+    pre_interaction: Option<(Address, Bytes)>,
+    taker_interaction: Option<(Address, Bytes)>,
+    post_interaction: Option<(Address, Bytes)>,
+}
+
+/// Pending token transfers of a batch, one per (token, from, to, pulled). Pulled transfers
+/// spend the sender's allowance to this contract (maker legs), the others the sender's auth.
+struct Transfers(Vec<(Address, Address, Address, bool, u128)>);
+
+impl Transfers {
+    fn new(env: &Env) -> Self {
+        Transfers(Vec::new(env))
+    }
+
+    /// Add a transfer pulled from `from` against its allowance
+    fn pull(&mut self, token: &Address, from: &Address, to: &Address, amount: u128) {
+        self.add(token, from, to, true, amount);
+    }
+
+    /// Add a transfer made by `from` itself
+    fn push(&mut self, token: &Address, from: &Address, to: &Address, amount: u128) {
+        self.add(token, from, to, false, amount);
+    }
+
+    fn add(&mut self, token: &Address, from: &Address, to: &Address, pulled: bool, amount: u128) {
+        if amount == 0 {
+            return;
+        }
+        for (i, (t, f, r, p, total)) in self.0.iter().enumerate() {
+            if t == *token && f == *from && r == *to && p == pulled {
+                self.0.set(i as u32, (t, f, r, p, total + amount));
+                return;
+            }
         }
+        self.0.push_back((token.clone(), from.clone(), to.clone(), pulled, amount));
     }
-}
 
-impl From<soroban_sdk::Error> for Error {
-    fn from(_err: soroban_sdk::Error) -> Self {
-        Error::TransferFailed
+    fn settle(self, env: &Env) {
+        for (token, from, to, pulled, amount) in self.0.iter() {
+            let client = token::Client::new(env, &token);
+            if pulled {
+                client.transfer_from(&env.current_contract_address(), &from, &to, &(amount as i128));
+            } else {
+                client.transfer(&from, &to, &(amount as i128));
+            }
+        }
     }
 }
 
-impl From<&Error> for soroban_sdk::Error {
-    fn from(err: &Error) -> Self {
-        err.clone().into()
-    }
+/// A fill as reported to interaction listeners
+struct Fill {
+    order_hash: BytesN<32>,
+    taker: Address,
+    making_amount: u128,
+    taking_amount: u128,
+    remaining: u128,     // Remaining making amount before the fill
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[contracterror]
+#[repr(u32)]
+pub enum Error {
+    TakingAmountExceeded = 1,
+    OrderExpired = 2,
+    SwapWithZeroAmount = 3,
+    BadSignature = 4,
+    TransferFailed = 5,
+    InvalidArgs = 6,
+    ConversionFailed = 7,
+    SignerNotBound = 8,
+    PrivateOrder = 9,
+    PartialFillNotAllowed = 10,
+    BitInvalidatedOrder = 11,
+    TakingAmountTooHigh = 12,
+    MakingAmountTooLow = 13,
+    WrongSeriesNonce = 14,
+    EpochManagerAndBitInvalidatorsAreIncompatible = 15,
+    InvalidatedOrder = 16,
+    PredicateIsNotTrue = 17,
+    InvalidPredicate = 18,
+    InvalidExtension = 19,
+    AmountOverflow = 20,
+    InvalidExpiration = 21,
+    OrderAlreadyPosted = 22,
+    GroupConsumed = 23,
+    TimeSliceExhausted = 24,
+    NotNativeAsset = 25,
+    NativeReserveNotMet = 26,
+    UnexpectedOrderExtension = 27,
 }
 
 #[cfg(test)]
@@ -0,0 +1,114 @@
+//! MakerTraits: order options packed into a u128 (Stellar counterpart of the EVM `MakerTraitsLib`)
+//!
+//! High bits are flags, low bits hold packed fields:
+//!
+//! | Bits     | Field                                                        |
+//! |----------|--------------------------------------------------------------|
+//! | 127      | NO_PARTIAL_FILLS                                             |
+//! | 126      | ALLOW_MULTIPLE_FILLS                                         |
+//! | 125      | PRE_INTERACTION_CALL                                         |
+//! | 124      | POST_INTERACTION_CALL                                        |
+//! | 123      | NEED_CHECK_EPOCH_MANAGER                                     |
+//! | 122      | HAS_EXTENSION: extension in the taker's args, hash commits   |
+//! | 121      | UNWRAP_NATIVE                                                |
+//! | 120      | ORDER_GROUP: custom data starts with the order group         |
+//! | 104..120 | series (16 bits)                                             |
+//! | 72..104  | nonce or epoch (32 bits)                                     |
+//! | 32..72   | expiration timestamp, 0 = never (40 bits)                    |
+//! | 0..32    | low 32 bits of the allowed sender's address payload, 0 = any |
+//!
+//! The EVM layout needs 256 bits; the fields are narrowed to fit the u128 used by
+//! `ResolverOrder` and `FactoryOrder`.
+//!
+//! The allowed-sender bits are a best-effort filter, not access control: an address with
+//! matching low bits takes about 2^32 tries to grind. Private orders name their taker in
+//! `Order::allowed_sender`, which is compared in full.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MakerTraits(pub u128);
+
+impl MakerTraits {
+    pub const NO_PARTIAL_FILLS_FLAG: u128 = 1 << 127;
+    pub const ALLOW_MULTIPLE_FILLS_FLAG: u128 = 1 << 126;
+    pub const PRE_INTERACTION_CALL_FLAG: u128 = 1 << 125;
+    pub const POST_INTERACTION_CALL_FLAG: u128 = 1 << 124;
+    pub const NEED_CHECK_EPOCH_MANAGER_FLAG: u128 = 1 << 123;
+    pub const HAS_EXTENSION_FLAG: u128 = 1 << 122;
+    pub const UNWRAP_NATIVE_FLAG: u128 = 1 << 121;
+    pub const ORDER_GROUP_FLAG: u128 = 1 << 120;
+
+    pub const ALLOWED_SENDER_MASK: u128 = 0xFFFF_FFFF;
+    pub const EXPIRATION_OFFSET: u32 = 32;
+    pub const EXPIRATION_MASK: u128 = 0xFF_FFFF_FFFF;
+    pub const NONCE_OR_EPOCH_OFFSET: u32 = 72;
+    pub const NONCE_OR_EPOCH_MASK: u128 = 0xFFFF_FFFF;
+    pub const SERIES_OFFSET: u32 = 104;
+    pub const SERIES_MASK: u128 = 0xFFFF;
+
+    fn flag(&self, flag: u128) -> bool {
+        self.0 & flag != 0
+    }
+
+    pub fn has_extension(&self) -> bool {
+        self.flag(Self::HAS_EXTENSION_FLAG)
+    }
+
+    /// Whether `sender` passes the allowed-sender filter. `sender` is the 32-byte address
+    /// payload (account key or contract id); its last four bytes are compared with the
+    /// allowed-sender bits, so this only screens out other takers by accident.
+    pub fn is_allowed_sender(&self, sender: &[u8; 32]) -> bool {
+        let allowed = (self.0 & Self::ALLOWED_SENDER_MASK) as u32;
+        allowed == 0 || allowed == u32::from_be_bytes([sender[28], sender[29], sender[30], sender[31]])
+    }
+
+    pub fn expiration_time(&self) -> u64 {
+        ((self.0 >> Self::EXPIRATION_OFFSET) & Self::EXPIRATION_MASK) as u64
+    }
+
+    /// Expired once the ledger timestamp passes the expiration time
+    pub fn is_expired(&self, now: u64) -> bool {
+        let expiration = self.expiration_time();
+        expiration != 0 && expiration < now
+    }
+
+    pub fn nonce_or_epoch(&self) -> u64 {
+        ((self.0 >> Self::NONCE_OR_EPOCH_OFFSET) & Self::NONCE_OR_EPOCH_MASK) as u64
+    }
+
+    pub fn series(&self) -> u64 {
+        ((self.0 >> Self::SERIES_OFFSET) & Self::SERIES_MASK) as u64
+    }
+
+    pub fn has_order_group(&self) -> bool {
+        self.flag(Self::ORDER_GROUP_FLAG)
+    }
+
+    pub fn allow_partial_fills(&self) -> bool {
+        !self.flag(Self::NO_PARTIAL_FILLS_FLAG)
+    }
+
+    pub fn need_pre_interaction_call(&self) -> bool {
+        self.flag(Self::PRE_INTERACTION_CALL_FLAG)
+    }
+
+    pub fn need_post_interaction_call(&self) -> bool {
+        self.flag(Self::POST_INTERACTION_CALL_FLAG)
+    }
+
+    pub fn allow_multiple_fills(&self) -> bool {
+        self.flag(Self::ALLOW_MULTIPLE_FILLS_FLAG)
+    }
+
+    /// Orders that cannot be partially filled or filled more than once are invalidated by nonce
+    pub fn use_bit_invalidator(&self) -> bool {
+        !self.allow_partial_fills() || !self.allow_multiple_fills()
+    }
+
+    pub fn need_check_epoch_manager(&self) -> bool {
+        self.flag(Self::NEED_CHECK_EPOCH_MANAGER_FLAG)
+    }
+
+    pub fn unwrap_native(&self) -> bool {
+        self.flag(Self::UNWRAP_NATIVE_FLAG)
+    }
+}
@@ -0,0 +1,61 @@
+//! Native XLM, held through the native Stellar asset contract (Stellar counterpart of the EVM
+//! WETH unwrapping)
+//!
+//! XLM needs no unwrapping on Stellar: the native SAC moves account balances directly. The
+//! unwrap flags instead pin the asset a party is paid in: a maker setting `UNWRAP_NATIVE`
+//! must take native XLM, and a taker setting it must receive native XLM.
+//!
+//! Native legs are checked against the account minimum reserve whichever flags are set: a
+//! paying account must keep `MIN_ACCOUNT_BALANCE` after the transfer, and a paid account
+//! must exist, since a payment cannot create one. Contracts can't read an account's base
+//! reserve or subentries, so the SAC still enforces the exact reserve on top of this.
+
+use soroban_sdk::{token, Address, Bytes, Env};
+
+use crate::{Error, MakerTraits, Order, StellarLimitOrderProtocol, TakerTraits};
+
+/// Two base reserves of 0.5 XLM, in stroops: the minimum balance of an account without subentries
+pub const MIN_ACCOUNT_BALANCE: i128 = 10_000_000;
+
+/// XDR of `Asset::Native`
+const NATIVE_ASSET_XDR: [u8; 4] = [0; 4];
+
+/// Address of the native SAC
+pub fn asset(env: &Env) -> Address {
+    env.deployer().with_stellar_asset(Bytes::from_array(env, &NATIVE_ASSET_XDR)).deployed_address()
+}
+
+/// The asset each unwrap flag pays in must be native XLM
+pub fn check_flags(env: &Env, order: &Order, taker_traits: &TakerTraits) -> Result<(), Error> {
+    let native = asset(env);
+    if (MakerTraits(order.maker_traits).unwrap_native() && order.taker_asset != native) || (taker_traits.unwrap_native && order.maker_asset != native) {
+        return Err(Error::NotNativeAsset);
+    }
+    Ok(())
+}
+
+/// Check that `from` can pay `amount` of `token` to `recipients` without breaking the minimum
+/// reserve of any account involved. Only native XLM legs are checked.
+pub fn check_transfer(env: &Env, token: &Address, from: &Address, amount: u128, recipients: impl Iterator<Item = Address>) -> Result<(), Error> {
+    if amount == 0 || *token != asset(env) {
+        return Ok(());
+    }
+    let native = token::Client::new(env, token);
+    if is_account(env, from) {
+        let balance = native.try_balance(from).ok().and_then(Result::ok).unwrap_or(0);
+        let needed = i128::try_from(amount).ok().and_then(|amount| amount.checked_add(MIN_ACCOUNT_BALANCE));
+        if needed.is_none_or(|needed| balance < needed) {
+            return Err(Error::NativeReserveNotMet);
+        }
+    }
+    for recipient in recipients.filter(|recipient| is_account(env, recipient)) {
+        if native.try_balance(&recipient).is_err() {
+            return Err(Error::NativeReserveNotMet);
+        }
+    }
+    Ok(())
+}
+
+fn is_account(env: &Env, address: &Address) -> bool {
+    StellarLimitOrderProtocol::address_to_public_key(env, address).is_ok()
+}
@@ -0,0 +1,117 @@
+//! On-chain order book: orders posted by their makers, indexed for discovery
+//!
+//! A posted order and its index slots are temporary entries that live until the order's
+//! expiration ledger, so expired orders drop out without cleanup. Each index (per asset pair
+//! and per maker) is an append-only list of order hashes: a persistent length plus one
+//! temporary entry per slot. Pages walk a bounded number of slots from a cursor and skip
+//! orders that expired, were filled or cancelled, or lost their group to a sibling, so a
+//! page can hold fewer orders than its limit while more follow.
+
+use soroban_sdk::{contracttype, Address, Bytes, BytesN, Env, Vec};
+
+use crate::{extension, order_group, DataKey, Error, MakerTraits, Order, OrderStatus, StellarLimitOrderProtocol};
+
+/// Most index slots a page walks
+pub const MAX_PAGE: u32 = 50;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OrderIndex {
+    Pair(Address, Address), // (maker_asset, taker_asset)
+    Maker(Address),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PostedOrder {
+    pub order: Order,
+    pub extension: Bytes,        // Args extension of HAS_EXTENSION orders; empty otherwise
+    pub signature: Bytes,        // Empty for orders approved through Soroban auth
+    pub expiration_ledger: u32,  // Last ledger the order is listed in
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ActiveOrder {
+    pub order_hash: BytesN<32>,
+    pub order: Order,
+    pub extension: Bytes,
+    pub signature: Bytes,
+    pub expiration_ledger: u32,
+    pub remaining: u128,         // Remaining making amount
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrderPage {
+    pub orders: Vec<ActiveOrder>,
+    pub next: u32,               // Cursor of the next page; the index length once all were read
+}
+
+/// Store a posted order and append it to the pair and maker indexes
+pub fn post(env: &Env, order_hash: &BytesN<32>, posted: &PostedOrder) -> Result<(), Error> {
+    let sequence = env.ledger().sequence();
+    if posted.expiration_ledger < sequence || posted.expiration_ledger > env.ledger().max_live_until_ledger() {
+        return Err(Error::InvalidExpiration);
+    }
+    if get(env, order_hash).is_some() {
+        return Err(Error::OrderAlreadyPosted);
+    }
+    let live_for = posted.expiration_ledger - sequence;
+    set_temporary(env, &DataKey::PostedOrder(order_hash.clone()), posted, live_for);
+    let order = &posted.order;
+    for index in [OrderIndex::Pair(order.maker_asset.clone(), order.taker_asset.clone()), OrderIndex::Maker(order.maker.clone())] {
+        let length = index_length(env, &index);
+        set_temporary(env, &DataKey::IndexSlot(index.clone(), length), order_hash, live_for);
+        StellarLimitOrderProtocol::set_persistent(env, &DataKey::IndexLength(index), &(length + 1));
+    }
+    Ok(())
+}
+
+/// The posted order behind `order_hash` until its expiration ledger passes
+pub fn get(env: &Env, order_hash: &BytesN<32>) -> Option<PostedOrder> {
+    let posted: PostedOrder = env.storage().temporary().get(&DataKey::PostedOrder(order_hash.clone()))?;
+    (posted.expiration_ledger >= env.ledger().sequence()).then_some(posted)
+}
+
+/// Active orders in slots `cursor..cursor + limit` of `index`, `limit` capped at `MAX_PAGE`
+pub fn page(env: &Env, index: OrderIndex, cursor: u32, limit: u32) -> OrderPage {
+    let length = index_length(env, &index);
+    let end = cursor.saturating_add(limit.min(MAX_PAGE)).min(length);
+    let mut orders = Vec::new(env);
+    for slot in cursor..end {
+        let order_hash: Option<BytesN<32>> = env.storage().temporary().get(&DataKey::IndexSlot(index.clone(), slot));
+        if let Some(active) = order_hash.and_then(|order_hash| active(env, order_hash)) {
+            orders.push_back(active);
+        }
+    }
+    OrderPage { orders, next: end.max(cursor) }
+}
+
+/// The posted order behind `order_hash` if it can still be filled
+fn active(env: &Env, order_hash: BytesN<32>) -> Option<ActiveOrder> {
+    let posted = get(env, &order_hash)?;
+    let order = extension::attach(posted.order.clone(), &posted.extension).ok()?;
+    if MakerTraits(order.maker_traits).is_expired(env.ledger().timestamp()) {
+        return None;
+    }
+    if let Ok(Some(group)) = order_group::of(&order) {
+        StellarLimitOrderProtocol::check_group(env, &group, &order_hash).ok()?;
+    }
+    let remaining = match StellarLimitOrderProtocol::order_status(env, order) {
+        (OrderStatus::Unfilled | OrderStatus::PartiallyFilled, remaining) => remaining,
+        _ => return None,
+    };
+    let PostedOrder { order, extension, signature, expiration_ledger } = posted;
+    Some(ActiveOrder { order_hash, order, extension, signature, expiration_ledger, remaining })
+}
+
+fn index_length(env: &Env, index: &OrderIndex) -> u32 {
+    env.storage().persistent().get(&DataKey::IndexLength(index.clone())).unwrap_or(0)
+}
+
+/// Write a temporary entry living for `live_for` more ledgers
+fn set_temporary<V: soroban_sdk::IntoVal<Env, soroban_sdk::Val>>(env: &Env, key: &DataKey, value: &V, live_for: u32) {
+    env.storage().temporary().set(key, value);
+    env.storage().temporary().extend_ttl(key, live_for, live_for);
+}
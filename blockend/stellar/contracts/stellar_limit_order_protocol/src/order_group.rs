@@ -0,0 +1,52 @@
+//! One-cancels-other order groups
+//!
+//! An order with the ORDER_GROUP maker trait starts the custom data of its extension with
+//! its group: a 32-byte group id and a 1-byte mode. Groups belong to the order's maker, so
+//! another maker reusing the id has a group of its own. A fill of a group member consumes the
+//! group for that order; fills of its siblings then fail with `GroupConsumed`.
+//!
+//! | Mode | Consumes the group                                                       |
+//! |------|--------------------------------------------------------------------------|
+//! | 0    | on the first fill, partial or not; only that order can fill on           |
+//! | 1    | on the fill that completes an order; until then siblings fill partially  |
+
+use soroban_sdk::{Address, Bytes, BytesN};
+
+use crate::{extension, Error, MakerTraits, Order};
+
+pub const CONSUME_ON_FILL: u8 = 0;
+pub const CONSUME_ON_FULL_FILL: u8 = 1;
+
+const GROUP_LEN: u32 = 33;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrderGroup {
+    pub maker: Address,
+    pub id: BytesN<32>,
+    pub mode: u8,
+}
+
+impl OrderGroup {
+    /// Whether a fill leaving `remaining` of the making amount consumes the group
+    pub fn consumed_by(&self, remaining: u128) -> bool {
+        self.mode == CONSUME_ON_FILL || remaining == 0
+    }
+}
+
+/// The order's group, if it belongs to one
+pub fn of(order: &Order) -> Result<Option<OrderGroup>, Error> {
+    if !MakerTraits(order.maker_traits).has_order_group() {
+        return Ok(None);
+    }
+    let custom = extension::custom_data(order)?;
+    if custom.len() < GROUP_LEN {
+        return Err(Error::InvalidExtension);
+    }
+    let mode = custom.get_unchecked(GROUP_LEN - 1);
+    if mode > CONSUME_ON_FULL_FILL {
+        return Err(Error::InvalidExtension);
+    }
+    let id: Bytes = custom.slice(..GROUP_LEN - 1);
+    let id = BytesN::try_from(id).map_err(|_| Error::InvalidExtension)?;
+    Ok(Some(OrderGroup { maker: order.maker.clone(), id, mode }))
+}
@@ -0,0 +1,130 @@
+//! Predicates: conditions an order must satisfy at fill time (Stellar counterpart of the EVM
+//! `PredicateHelper`)
+//!
+//! A predicate is an expression tree serialized in prefix form. Integers are big-endian;
+//! XDR values are prefixed with their length as a u32.
+//!
+//! | Tag  | Node              | Operands                                      | True when                   |
+//! |------|-------------------|-----------------------------------------------|-----------------------------|
+//! | 0x01 | `and`             | u8 count, `count` predicates                  | all are true                |
+//! | 0x02 | `or`              | u8 count, `count` predicates                  | any is true                 |
+//! | 0x03 | `not`             | predicate                                     | it is false                 |
+//! | 0x04 | `lt`              | i128 value, call                              | call result < value         |
+//! | 0x05 | `gt`              | i128 value, call                              | call result > value         |
+//! | 0x06 | `eq`              | i128 value, call                              | call result == value        |
+//! | 0x07 | `timestamp_below` | u64 timestamp                                 | ledger timestamp < value    |
+//! | 0x08 | `epoch_equals`    | u64 series, u64 epoch                         | maker's epoch for series == epoch |
+//!
+//! A call is a contract address, a function symbol and a `Vec<Val>` of arguments, each as
+//! length-prefixed XDR, and must return an i128. Soroban has no static calls, so makers should
+//! only reference view functions; the callee cannot re-enter the LOP. `and`/`or` short-circuit
+//! and skipped branches make no calls.
+
+use soroban_sdk::{xdr::FromXdr, Address, Bytes, Env, Symbol, Val, Vec};
+
+use crate::{Error, Order, StellarLimitOrderProtocol};
+
+pub const AND: u8 = 0x01;
+pub const OR: u8 = 0x02;
+pub const NOT: u8 = 0x03;
+pub const LT: u8 = 0x04;
+pub const GT: u8 = 0x05;
+pub const EQ: u8 = 0x06;
+pub const TIMESTAMP_BELOW: u8 = 0x07;
+pub const EPOCH_EQUALS: u8 = 0x08;
+
+/// Maximum nesting of `and`/`or`/`not`
+const MAX_DEPTH: u32 = 16;
+
+/// Evaluate a predicate for `order`; malformed predicates and failing calls are `InvalidPredicate`
+pub fn evaluate(env: &Env, order: &Order, predicate: &Bytes) -> Result<bool, Error> {
+    let mut reader = Reader { bytes: predicate.clone(), pos: 0 };
+    let result = node(env, order, &mut reader, true, 0)?;
+    if reader.pos != predicate.len() {
+        return Err(Error::InvalidPredicate);
+    }
+    Ok(result)
+}
+
+/// Parse one node, evaluating it only when `eval` is set (skipped branches are only parsed)
+fn node(env: &Env, order: &Order, reader: &mut Reader, eval: bool, depth: u32) -> Result<bool, Error> {
+    if depth > MAX_DEPTH {
+        return Err(Error::InvalidPredicate);
+    }
+    match reader.u8()? {
+        tag @ (AND | OR) => {
+            let is_and = tag == AND;
+            let count = reader.u8()?;
+            // `and` stays true until a false operand, `or` false until a true one
+            let mut result = is_and;
+            for _ in 0..count {
+                let operand = node(env, order, reader, eval && result == is_and, depth + 1)?;
+                if eval && result == is_and {
+                    result = operand;
+                }
+            }
+            Ok(result)
+        }
+        NOT => Ok(!node(env, order, reader, eval, depth + 1)?),
+        tag @ (LT | GT | EQ) => {
+            let value = reader.i128()?;
+            let (contract, function, args) = (reader.xdr::<Address>(env)?, reader.xdr::<Symbol>(env)?, reader.xdr::<Vec<Val>>(env)?);
+            if !eval {
+                return Ok(false);
+            }
+            let result = match env.try_invoke_contract::<i128, Error>(&contract, &function, args) {
+                Ok(Ok(result)) => result,
+                _ => return Err(Error::InvalidPredicate),
+            };
+            Ok(match tag {
+                LT => result < value,
+                GT => result > value,
+                _ => result == value,
+            })
+        }
+        TIMESTAMP_BELOW => Ok(env.ledger().timestamp() < reader.u64()?),
+        EPOCH_EQUALS => {
+            let (series, epoch) = (reader.u64()?, reader.u64()?);
+            Ok(eval && StellarLimitOrderProtocol::epoch(env, order.maker.clone(), series) == epoch)
+        }
+        _ => Err(Error::InvalidPredicate),
+    }
+}
+
+struct Reader {
+    bytes: Bytes,
+    pos: u32,
+}
+
+impl Reader {
+    fn take(&mut self, len: u32) -> Result<Bytes, Error> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len()).ok_or(Error::InvalidPredicate)?;
+        let data = self.bytes.slice(self.pos..end);
+        self.pos = end;
+        Ok(data)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut out = [0u8; N];
+        self.take(N as u32)?.copy_into_slice(&mut out);
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    fn i128(&mut self) -> Result<i128, Error> {
+        Ok(i128::from_be_bytes(self.array()?))
+    }
+
+    fn xdr<T: FromXdr>(&mut self, env: &Env) -> Result<T, Error> {
+        let len = u32::from_be_bytes(self.array()?);
+        let data = self.take(len)?;
+        T::from_xdr(env, &data).map_err(|_| Error::InvalidPredicate)
+    }
+}
@@ -0,0 +1,49 @@
+//! RemainingInvalidator: stored fill state of an order (Stellar counterpart of the EVM
+//! `RemainingInvalidatorLib`)
+//!
+//! Nothing is stored until an order's first fill or cancellation. Fills store the remaining
+//! making amount inverted, as the EVM word does (`u128::MAX` once fully filled); cancellation
+//! stores a marker of its own, so cancelled and fully filled orders stay distinguishable.
+
+use soroban_sdk::contracttype;
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RemainingInvalidator {
+    Remains(u128),  // !remaining making amount
+    Cancelled,
+}
+
+/// Fill status reported by `order_status`
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum OrderStatus {
+    Unfilled = 0,
+    PartiallyFilled = 1,
+    FullyFilled = 2,
+    Cancelled = 3,
+}
+
+impl RemainingInvalidator {
+    /// State after a fill leaves `remaining` of the making amount
+    pub fn remains(remaining: u128) -> Self {
+        RemainingInvalidator::Remains(!remaining)
+    }
+
+    /// Remaining making amount; `None` once cancelled
+    pub fn remaining(&self) -> Option<u128> {
+        match self {
+            RemainingInvalidator::Remains(inverted) => Some(!inverted),
+            RemainingInvalidator::Cancelled => None,
+        }
+    }
+
+    /// The raw EVM word: `!remaining`, and `u128::MAX` (fully filled) for cancelled orders
+    pub fn word(&self) -> u128 {
+        match self {
+            RemainingInvalidator::Remains(inverted) => *inverted,
+            RemainingInvalidator::Cancelled => u128::MAX,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! TakerTraits: fill options chosen by the taker (Stellar counterpart of the EVM `TakerTraitsLib`)
+//!
+//! `fill_args` receives the EVM `uint256` layout as an `I256`:
+//!
+//! | Bits     | Field                                                             |
+//! |----------|-------------------------------------------------------------------|
+//! | 255      | MAKER_AMOUNT: `amount` is a making amount                         |
+//! | 254      | UNWRAP_NATIVE: taker receives native XLM                          |
+//! | 253      | SKIP_ORDER_PERMIT                                                 |
+//! | 251      | ARGS_HAS_TARGET: `args` starts with the target address            |
+//! | 224..248 | length of the extension in `args` (24 bits)                       |
+//! | 200..224 | length of the taker interaction in `args` (24 bits)               |
+//! | 0..185   | threshold: max taking amount (making mode) or min making amount   |
+
+use soroban_sdk::{contracttype, I256};
+
+use crate::bits::Word256;
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TakerTraits {
+    pub threshold: u128,
+    pub skip_maker_permit: bool,
+    pub maker_amount: bool,           // `amount` is a making amount instead of a taking amount
+    pub unwrap_native: bool,
+    pub args_has_target: bool,
+    pub args_extension_length: u32,
+    pub args_interaction_length: u32,
+}
+
+impl TakerTraits {
+    pub const MAKER_AMOUNT_FLAG: u32 = 255;
+    pub const UNWRAP_NATIVE_FLAG: u32 = 254;
+    pub const SKIP_ORDER_PERMIT_FLAG: u32 = 253;
+    pub const ARGS_HAS_TARGET: u32 = 251;
+
+    pub const ARGS_EXTENSION_LENGTH_OFFSET: u32 = 224;
+    pub const ARGS_INTERACTION_LENGTH_OFFSET: u32 = 200;
+    pub const ARGS_LENGTH_WIDTH: u32 = 24;
+    pub const THRESHOLD_WIDTH: u32 = 185;
+
+    /// Decode the EVM word. Thresholds above u128 saturate: unlimited in making mode,
+    /// unreachable in taking mode, as they would be on EVM.
+    pub fn from_i256(value: &I256) -> Self {
+        let word = Word256::from_i256(value);
+        let threshold = if word.any(128, Self::THRESHOLD_WIDTH - 128) {
+            u128::MAX
+        } else {
+            word.field(0, 128)
+        };
+        TakerTraits {
+            threshold,
+            skip_maker_permit: word.bit(Self::SKIP_ORDER_PERMIT_FLAG),
+            maker_amount: word.bit(Self::MAKER_AMOUNT_FLAG),
+            unwrap_native: word.bit(Self::UNWRAP_NATIVE_FLAG),
+            args_has_target: word.bit(Self::ARGS_HAS_TARGET),
+            args_extension_length: word.field(Self::ARGS_EXTENSION_LENGTH_OFFSET, Self::ARGS_LENGTH_WIDTH) as u32,
+            args_interaction_length: word.field(Self::ARGS_INTERACTION_LENGTH_OFFSET, Self::ARGS_LENGTH_WIDTH) as u32,
+        }
+    }
+}
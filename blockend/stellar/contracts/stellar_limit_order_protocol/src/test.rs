@@ -1,150 +1,2803 @@
 #![cfg(test)]
+extern crate std;
 
 use super::*;
+use ed25519_dalek::{Signer, SigningKey};
 use soroban_sdk::{
-    symbol_short, vec, Address, Bytes, BytesN, Env, Symbol,
+    contract, contractimpl, symbol_short,
+    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger, MockAuth, MockAuthInvoke},
+    token, vec,
+    xdr::{
+        AccountEntry, AccountEntryExt, AccountId, ContractCostType, FromXdr, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey,
+        LedgerKeyAccount, PublicKey, SequenceNumber, Thresholds, ToXdr, Uint256,
+    },
+    Address, Bytes, BytesN, Env, IntoVal, String, Symbol, TryFromVal, Val, I256,
 };
 
-#[test]
-fn test_initialize() {
-    let env = Env::default();
-    let contract_id = env.register_contract(None, StellarLimitOrderProtocol);
-    let client = StellarLimitOrderProtocolClient::new(&env, &contract_id);
+// Minimal token used as maker/taker asset. It keeps balances in contract storage so
+// account-address makers don't need trustlines in the test ledger.
+#[contract]
+pub struct TestToken;
 
-    client.initialize();
-    
-    // Verify storage is initialized
-    assert!(env.storage().instance().has(&StellarLimitOrderProtocol::BIT_INVALIDATOR));
-    assert!(env.storage().instance().has(&StellarLimitOrderProtocol::REMAINING_INVALIDATOR));
-    assert!(env.storage().instance().has(&StellarLimitOrderProtocol::ORDERS));
+#[contractimpl]
+impl TestToken {
+    pub fn mint(env: Env, to: Address, amount: i128) {
+        let balance = Self::balance(env.clone(), to.clone());
+        env.storage().persistent().set(&to, &(balance + amount));
+    }
+
+    pub fn balance(env: Env, id: Address) -> i128 {
+        env.storage().persistent().get(&id).unwrap_or(0)
+    }
+
+    pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+        Self::move_balance(&env, from, to, amount);
+    }
+
+    // Allowances don't expire
+    pub fn approve(env: Env, from: Address, spender: Address, amount: i128, _expiration_ledger: u32) {
+        from.require_auth();
+        env.storage().persistent().set(&(from, spender), &amount);
+    }
+
+    pub fn allowance(env: Env, from: Address, spender: Address) -> i128 {
+        env.storage().persistent().get(&(from, spender)).unwrap_or(0)
+    }
+
+    pub fn transfer_from(env: Env, spender: Address, from: Address, to: Address, amount: i128) {
+        spender.require_auth();
+        let allowance = Self::allowance(env.clone(), from.clone(), spender.clone());
+        if allowance < amount {
+            panic!("insufficient allowance");
+        }
+        env.storage().persistent().set(&(from.clone(), spender), &(allowance - amount));
+        Self::move_balance(&env, from, to, amount);
+    }
+
+    pub fn transfer_count(env: Env) -> u32 {
+        env.storage().instance().get(&symbol_short!("transfers")).unwrap_or(0)
+    }
 }
 
-#[test]
-fn test_hash_order() {
-    let env = Env::default();
+impl TestToken {
+    fn move_balance(env: &Env, from: Address, to: Address, amount: i128) {
+        let from_balance = Self::balance(env.clone(), from.clone());
+        if from_balance < amount {
+            panic!("insufficient balance");
+        }
+        env.storage().persistent().set(&from, &(from_balance - amount));
+        let to_balance = Self::balance(env.clone(), to.clone());
+        env.storage().persistent().set(&to, &(to_balance + amount));
+        let transfers = Self::transfer_count(env.clone());
+        env.storage().instance().set(&symbol_short!("transfers"), &(transfers + 1));
+    }
+}
+
+// Records the arguments of the last post_interaction call, standing in for the escrow factory
+#[contract]
+pub struct TestFactory;
+
+#[contractimpl]
+impl TestFactory {
+    #[allow(clippy::too_many_arguments)]
+    pub fn post_interaction(
+        env: Env,
+        order: FactoryOrder,
+        _extension: Bytes,
+        order_hash: BytesN<32>,
+        taker: Address,
+        making_amount: u128,
+        taking_amount: u128,
+        _remaining_making_amount: u128,
+        extra_data: Bytes,
+    ) {
+        env.storage().instance().set(&symbol_short!("order"), &order);
+        env.storage().instance().set(&symbol_short!("hash"), &order_hash);
+        env.storage().instance().set(&symbol_short!("taker"), &taker);
+        env.storage().instance().set(&symbol_short!("amounts"), &(making_amount, taking_amount));
+        env.storage().instance().set(&symbol_short!("extra"), &extra_data);
+    }
+}
+
+// Interaction listener: records each call and, as a taker interaction, mints the taking
+// amount to the taker the way a resolver would source it just in time. It has its own
+// module so its post_interaction doesn't clash with the factory's.
+mod listener {
+    use super::*;
+
+    #[contract]
+    pub struct TestListener;
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_interaction(env: &Env, hook: Symbol, order: &FactoryOrder, taker: &Address, making_amount: u128, taking_amount: u128, remaining: u128, extra_data: Bytes) {
+        let calls: u32 = env.storage().instance().get(&symbol_short!("calls")).unwrap_or(0);
+        env.storage().instance().set(&symbol_short!("calls"), &(calls + 1));
+        let received = TestTokenClient::new(env, &order.maker_asset).balance(taker);
+        env.storage().instance().set(&hook, &(calls, received, making_amount, taking_amount, remaining, extra_data));
+    }
+
+    #[contractimpl]
+    impl TestListener {
+        #[allow(clippy::too_many_arguments)]
+        pub fn pre_interaction(env: Env, order: FactoryOrder, _extension: Bytes, _order_hash: BytesN<32>, taker: Address, making_amount: u128, taking_amount: u128, remaining: u128, extra_data: Bytes) {
+            record_interaction(&env, symbol_short!("pre"), &order, &taker, making_amount, taking_amount, remaining, extra_data);
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn taker_interaction(env: Env, order: FactoryOrder, _extension: Bytes, _order_hash: BytesN<32>, taker: Address, making_amount: u128, taking_amount: u128, remaining: u128, extra_data: Bytes) {
+            record_interaction(&env, symbol_short!("taker"), &order, &taker, making_amount, taking_amount, remaining, extra_data);
+            TestTokenClient::new(&env, &order.taker_asset).mint(&taker, &(taking_amount as i128));
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn post_interaction(env: Env, order: FactoryOrder, _extension: Bytes, _order_hash: BytesN<32>, taker: Address, making_amount: u128, taking_amount: u128, remaining: u128, extra_data: Bytes) {
+            record_interaction(&env, symbol_short!("post"), &order, &taker, making_amount, taking_amount, remaining, extra_data);
+        }
+    }
+}
+use listener::TestListener;
+
+// Price feed referenced by predicates
+#[contract]
+pub struct TestOracle;
+
+#[contractimpl]
+impl TestOracle {
+    pub fn set_price(env: Env, price: i128) {
+        env.storage().instance().set(&symbol_short!("price"), &price);
+    }
+
+    pub fn price(env: Env) -> i128 {
+        env.storage().instance().get(&symbol_short!("price")).unwrap_or(0)
+    }
+}
+
+struct Setup<'a> {
+    env: Env,
+    client: StellarLimitOrderProtocolClient<'a>,
+    contract_id: Address,
+    maker_key: SigningKey,
+    maker: Address,
+    taker: Address,
+    maker_asset: TestTokenClient<'a>,
+    taker_asset: TestTokenClient<'a>,
+}
+
+/// Build the Stellar account address (G...) for an ed25519 keypair
+fn account_address(env: &Env, key: &SigningKey) -> Address {
+    let mut xdr = [0u8; 44];
+    xdr[..12].copy_from_slice(&StellarLimitOrderProtocol::ACCOUNT_XDR_HEADER);
+    xdr[12..].copy_from_slice(key.verifying_key().as_bytes());
+    Address::from_xdr(env, &Bytes::from_array(env, &xdr)).unwrap()
+}
+
+fn setup<'a>() -> Setup<'a> {
+    setup_in(Env::default())
+}
+
+fn setup_in<'a>(env: Env) -> Setup<'a> {
+    // Taker payments are covered by the taker's authorization of the fill
+    env.mock_all_auths();
+
     let contract_id = env.register_contract(None, StellarLimitOrderProtocol);
     let client = StellarLimitOrderProtocolClient::new(&env, &contract_id);
+    client.initialize(&Address::generate(&env));
+
+    let maker_key = SigningKey::from_bytes(&[7u8; 32]);
+    let maker = account_address(&env, &maker_key);
+    let taker = Address::generate(&env);
+
+    let maker_asset = TestTokenClient::new(&env, &env.register_contract(None, TestToken));
+    let taker_asset = TestTokenClient::new(&env, &env.register_contract(None, TestToken));
+    maker_asset.mint(&maker, &1_000_000);
+    maker_asset.approve(&maker, &contract_id, &i128::MAX, &u32::MAX);
+    taker_asset.mint(&taker, &1_000_000);
+
+    Setup { env, client, contract_id, maker_key, maker, taker, maker_asset, taker_asset }
+}
 
-    let maker = Address::generate(&env);
-    let receiver = Address::generate(&env);
-    let maker_asset = Address::generate(&env);
-    let taker_asset = Address::generate(&env);
+/// Mint maker assets to `maker` and approve the LOP to pull them, as makers do before signing
+fn fund_maker(s: &Setup, maker: &Address, amount: i128) {
+    s.maker_asset.mint(maker, &amount);
+    s.maker_asset.approve(maker, &s.contract_id, &i128::MAX, &u32::MAX);
+}
 
-    let order = Order {
+fn build_order(s: &Setup) -> Order {
+    Order {
         salt: 12345,
-        maker: maker.clone(),
-        receiver: receiver.clone(),
-        maker_asset: maker_asset.clone(),
-        taker_asset: taker_asset.clone(),
+        maker_asset: s.maker_asset.address.clone(),
+        taker_asset: s.taker_asset.address.clone(),
+        maker: s.maker.clone(),
+        receiver: s.maker.clone(),
+        allowed_sender: s.client.any_sender(),
         making_amount: 1000,
         taking_amount: 500,
         maker_traits: 0,
-    };
+        offsets: 0,
+        interactions: Bytes::new(&s.env),
+        evm_signer: BytesN::from_array(&s.env, &[0u8; 20]),
+    }
+}
 
-    let hash1 = client.hash_order(&order);
-    let hash2 = client.hash_order(&order);
-    
-    // Same order should produce same hash
-    assert_eq!(hash1, hash2);
+fn sign_order(s: &Setup, key: &SigningKey, order: &Order) -> Bytes {
+    let order_hash = s.client.hash_order(order);
+    Bytes::from_array(&s.env, &key.sign(&order_hash.to_array()).to_bytes())
+}
+
+/// Ethereum address of a secp256k1 key
+fn evm_address(env: &Env, key: &k256::ecdsa::SigningKey) -> BytesN<20> {
+    let point = key.verifying_key().to_encoded_point(false);
+    let key_hash = env.crypto().keccak256(&Bytes::from_slice(env, &point.as_bytes()[1..]));
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&key_hash.to_array()[12..]);
+    BytesN::from_array(env, &address)
+}
+
+/// Sign the order hash the way an EVM wallet does: `r || s || v` with v = 27/28
+fn sign_order_evm(s: &Setup, key: &k256::ecdsa::SigningKey, order: &Order) -> Bytes {
+    let order_hash = s.client.hash_order(order);
+    let (signature, recovery_id) = key.sign_prehash_recoverable(&order_hash.to_array()).unwrap();
+    let mut sig_bytes = Bytes::from_slice(&s.env, signature.to_bytes().as_slice());
+    sig_bytes.push_back(27 + recovery_id.to_byte());
+    sig_bytes
+}
+
+/// Order from a contract-held funding account signed by an EVM key bound to it
+fn evm_order(s: &Setup, key: &k256::ecdsa::SigningKey) -> (Order, Address) {
+    let funding = Address::generate(&s.env);
+    fund_maker(s, &funding, 1000);
+    let mut order = build_order(s);
+    order.maker = funding.clone();
+    order.receiver = funding.clone();
+    order.evm_signer = evm_address(&s.env, key);
+    (order, funding)
+}
+
+fn taker_traits() -> TakerTraits {
+    TakerTraits {
+        threshold: 0,
+        skip_maker_permit: false,
+        maker_amount: false,
+        unwrap_native: false,
+        args_has_target: false,
+        args_extension_length: 0,
+        args_interaction_length: 0,
+    }
 }
 
 #[test]
-fn test_fill_order_basic() {
+fn test_initialize() {
     let env = Env::default();
     let contract_id = env.register_contract(None, StellarLimitOrderProtocol);
     let client = StellarLimitOrderProtocolClient::new(&env, &contract_id);
 
-    client.initialize();
+    let factory = Address::generate(&env);
+    client.initialize(&factory);
 
-    let maker = Address::generate(&env);
-    let receiver = Address::generate(&env);
-    let maker_asset = Address::generate(&env);
-    let taker_asset = Address::generate(&env);
-    let taker = Address::generate(&env);
+    // Verify storage is initialized
+    env.as_contract(&contract_id, || {
+        let stored: Address = env.storage().instance().get(&StellarLimitOrderProtocol::FACTORY).unwrap();
+        assert_eq!(stored, factory);
+    });
+}
 
-    let order = Order {
-        salt: env.ledger().timestamp() + 3600, // Future timestamp
-        maker: maker.clone(),
-        receiver: receiver.clone(),
-        maker_asset: maker_asset.clone(),
-        taker_asset: taker_asset.clone(),
-        making_amount: 1000,
-        taking_amount: 500,
+#[test]
+fn test_hash_order() {
+    let s = setup();
+    let order = build_order(&s);
+
+    let hash1 = s.client.hash_order(&order);
+    let hash2 = s.client.hash_order(&order);
+
+    // Same order should produce same hash
+    assert_eq!(hash1, hash2);
+}
+
+fn hex32(hex: &str) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+    }
+    out
+}
+
+fn strkey(env: &Env, strkey: &str) -> Address {
+    Address::from_string(&String::from_str(env, strkey))
+}
+
+/// Environment of the published hashing test vectors (see ORDER_HASHING.md)
+fn vector_env() -> (Env, StellarLimitOrderProtocolClient<'static>) {
+    let env = Env::default();
+    let network_id = env.crypto().sha256(&Bytes::from_slice(&env, b"Test SDF Network ; September 2015"));
+    env.ledger().with_mut(|li| li.network_id = network_id.to_array());
+    let contract_id = strkey(&env, "CAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAODX");
+    env.register_contract(Some(&contract_id), StellarLimitOrderProtocol);
+    let client = StellarLimitOrderProtocolClient::new(&env, &contract_id);
+    (env, client)
+}
+
+fn vector_order(env: &Env) -> Order {
+    Order {
+        salt: 1,
+        maker_asset: strkey(env, "CAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAKAL"),
+        taker_asset: strkey(env, "CAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDBWBA"),
+        maker: strkey(env, "GBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEBGCK"),
+        receiver: strkey(env, "GBIFAUCQKBIFAUCQKBIFAUCQKBIFAUCQKBIFAUCQKBIFAUCQKBIFA2DB"),
+        allowed_sender: strkey(env, "GBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGAYDAMBQGA6A5"),
+        making_amount: 1_000_000_000,
+        taking_amount: 500_000_000,
         maker_traits: 0,
-    };
+        offsets: 0,
+        interactions: Bytes::new(env),
+        evm_signer: BytesN::from_array(env, &[0u8; 20]),
+    }
+}
 
-    let signature = Bytes::from_slice(&env, &[0u8; 64]); // Mock signature
-    let taker_traits = TakerTraits {
-        threshold: 1000,
-        skip_maker_permit: false,
-    };
+#[test]
+fn test_domain_separator_vector() {
+    let (_env, client) = vector_env();
+    assert_eq!(
+        client.domain_separator().to_array(),
+        hex32("9273062c2ca7a0ccb028ff3438447991b5f3f6dca216c15d13f77df5bb549822")
+    );
+}
+
+#[test]
+fn test_hash_order_vectors() {
+    let (env, client) = vector_env();
+
+    let order = vector_order(&env);
+    assert_eq!(
+        client.hash_order(&order).to_array(),
+        hex32("5ef1c945d362714f64dda8bfccb566d298000ef70ed51e2ab161e9054f7fa62b")
+    );
+
+    let mut order = vector_order(&env);
+    order.salt = u64::MAX;
+    order.receiver = order.maker.clone();
+    order.making_amount = u128::MAX;
+    order.taking_amount = 1;
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG | (1_700_000_000 << MakerTraits::EXPIRATION_OFFSET);
+    order.offsets = 7;
+    order.interactions = Bytes::from_array(&env, &[1, 2, 3]);
+    order.evm_signer = BytesN::from_array(&env, &[0x11; 20]);
+    assert_eq!(
+        client.hash_order(&order).to_array(),
+        hex32("ad11110de2020284306a5f0a995800f87b719e283c347c610b783c714561984d")
+    );
+
+    // HAS_EXTENSION: the offsets word and interactions give way to the extension's hash
+    let mut order = vector_order(&env);
+    order.maker_traits = MakerTraits::HAS_EXTENSION_FLAG;
+    order.interactions = Bytes::from_array(&env, &[1, 2, 3]);
+    assert_eq!(
+        client.hash_order(&order).to_array(),
+        hex32("afe66507f02ecc4b71703b1f5be86bbc04eeac9a51478153957018af4cde613a")
+    );
+}
+
+#[test]
+fn test_hash_order_bound_to_deployment() {
+    let (env, client) = vector_env();
+    let order = vector_order(&env);
+    let hash = client.hash_order(&order);
+
+    // Same order on another LOP deployment
+    let other = StellarLimitOrderProtocolClient::new(&env, &env.register_contract(None, StellarLimitOrderProtocol));
+    assert_ne!(other.hash_order(&order), hash);
+
+    // Same order on another network
+    env.ledger().with_mut(|li| li.network_id = [0xff; 32]);
+    assert_ne!(client.hash_order(&order), hash);
+}
+
+#[test]
+fn test_address_to_public_key() {
+    let s = setup();
+    let public_key = s.env.as_contract(&s.contract_id, || {
+        StellarLimitOrderProtocol::address_to_public_key(&s.env, &s.maker).unwrap()
+    });
+    assert_eq!(public_key.to_array(), *s.maker_key.verifying_key().as_bytes());
+}
+
+#[test]
+fn test_fill_order_basic() {
+    let s = setup();
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let (making_amount, taking_amount, order_hash) =
+        s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
 
-    let result = client.fill_order(&order, &signature, &taker, &100, &taker_traits);
-    
-    // Should succeed (though signature verification is mocked)
-    assert!(result.is_ok());
-    
-    let (making_amount, taking_amount, order_hash) = result.unwrap();
     assert_eq!(making_amount, 200); // (100 * 1000) / 500
     assert_eq!(taking_amount, 100);
-    assert_eq!(order_hash.len(), 32);
+    assert_eq!(order_hash, s.client.hash_order(&order));
+
+    assert_eq!(s.maker_asset.balance(&s.maker), 1_000_000 - 200);
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+    assert_eq!(s.taker_asset.balance(&s.maker), 100);
+    assert_eq!(s.taker_asset.balance(&s.taker), 1_000_000 - 100);
+}
+
+/// Authorize only the taker's `fill_order` call and, under it, its payment of `taking_amount`
+/// to the maker
+fn mock_taker_fill(s: &Setup, order: &Order, signature: &Bytes, amount: u128, taking_amount: i128) {
+    s.env.mock_auths(&[MockAuth {
+        address: &s.taker,
+        invoke: &MockAuthInvoke {
+            contract: &s.contract_id,
+            fn_name: "fill_order",
+            args: (order.clone(), signature.clone(), s.taker.clone(), amount, taker_traits()).into_val(&s.env),
+            sub_invokes: &[MockAuthInvoke {
+                contract: &s.taker_asset.address,
+                fn_name: "transfer",
+                args: (s.taker.clone(), s.maker.clone(), taking_amount).into_val(&s.env),
+                sub_invokes: &[],
+            }],
+        },
+    }]);
+}
+
+#[test]
+fn test_fill_signed_order_without_maker_auth() {
+    let s = setup();
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    // Only the taker authorizes: the maker leg spends the maker's allowance
+    mock_taker_fill(&s, &order, &signature, 100, 100);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+    assert_eq!(s.taker_asset.balance(&s.maker), 100);
+    assert_eq!(s.maker_asset.allowance(&s.maker, &s.contract_id), i128::MAX - 200);
+}
+
+#[test]
+fn test_taker_authorizes_fill_args() {
+    let s = setup();
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let target = Address::generate(&s.env);
+    let traits = TakerTraits { args_has_target: true, ..taker_traits() };
+    let args = args_with_target(&s.env, &target, &[]);
+
+    // The taker's authorization covers the args, target included, and the payment under it
+    s.client.fill_order_args(&order, &signature, &s.taker, &100, &traits, &args);
+    assert_eq!(
+        s.env.auths(),
+        std::vec![(
+            s.taker.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    s.contract_id.clone(),
+                    Symbol::new(&s.env, "fill_order_args"),
+                    (order, signature, s.taker.clone(), 100u128, traits, args).into_val(&s.env),
+                )),
+                sub_invocations: std::vec![AuthorizedInvocation {
+                    function: AuthorizedFunction::Contract((
+                        s.taker_asset.address.clone(),
+                        Symbol::new(&s.env, "transfer"),
+                        (s.taker.clone(), s.maker.clone(), 100i128).into_val(&s.env),
+                    )),
+                    sub_invocations: std::vec![],
+                }],
+            }
+        )]
+    );
+    assert_eq!(s.maker_asset.balance(&target), 200);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_fill_requires_taker_auth() {
+    let s = setup();
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.env.set_auths(&[]);
+
+    let _ = fill_direct(&s, &order, &signature, 100);
+}
+
+/// Call `fill_order` inside the contract frame. Host traps (failed ed25519 verification)
+/// surface as test panics this way instead of aborting through the client.
+fn fill_direct(s: &Setup, order: &Order, signature: &Bytes, amount: u128) -> Result<(u128, u128, BytesN<32>), Error> {
+    s.env.as_contract(&s.contract_id, || {
+        StellarLimitOrderProtocol::fill_order(&s.env, order.clone(), signature.clone(), s.taker.clone(), amount, taker_traits())
+    })
+}
+
+#[test]
+#[should_panic(expected = "Error(Crypto, InvalidInput)")]
+fn test_fill_order_tampered_order() {
+    let s = setup();
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // Maker signed 1000 -> 500; taker tries to claim a better rate
+    let mut tampered = order.clone();
+    tampered.making_amount = 2000;
+
+    let _ = fill_direct(&s, &tampered, &signature, 100);
+}
+
+#[test]
+#[should_panic(expected = "Error(Crypto, InvalidInput)")]
+fn test_fill_order_tampered_signature() {
+    let s = setup();
+    let order = build_order(&s);
+    let mut sig_bytes = [0u8; 64];
+    sign_order(&s, &s.maker_key, &order).copy_into_slice(&mut sig_bytes);
+    sig_bytes[10] ^= 0x01;
+    let signature = Bytes::from_array(&s.env, &sig_bytes);
+
+    let _ = fill_direct(&s, &order, &signature, 100);
+}
+
+#[test]
+#[should_panic(expected = "Error(Crypto, InvalidInput)")]
+fn test_fill_order_wrong_signer() {
+    let s = setup();
+    let order = build_order(&s);
+    let other_key = SigningKey::from_bytes(&[9u8; 32]);
+    let signature = sign_order(&s, &other_key, &order);
+
+    let _ = fill_direct(&s, &order, &signature, 100);
+}
+
+#[test]
+fn test_fill_order_bad_signature_length() {
+    let s = setup();
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order).slice(0..63);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BadSignature)));
+}
+
+#[test]
+fn test_fill_order_contract_maker() {
+    let s = setup();
+    let mut order = build_order(&s);
+    // Contract addresses have no ed25519 key to sign with
+    order.maker = Address::generate(&s.env);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BadSignature)));
+}
+
+/// Whether `maker` authorized a `fill_order` call for exactly `(order_hash, amount)`
+fn maker_authorized_fill(s: &Setup, maker: &Address, order_hash: &BytesN<32>, amount: u128) -> bool {
+    let expected_args = vec![&s.env, order_hash.into_val(&s.env), amount.into_val(&s.env)];
+    s.env.auths().iter().any(|(address, invocation)| {
+        address == maker
+            && invocation.function
+                == AuthorizedFunction::Contract((
+                    s.contract_id.clone(),
+                    Symbol::new(&s.env, "fill_order"),
+                    expected_args.clone(),
+                ))
+    })
+}
+
+#[test]
+fn test_fill_order_soroban_auth() {
+    let s = setup();
+    let mut order = build_order(&s);
+    // Smart-wallet maker: a contract address that cannot produce an ed25519 signature
+    let wallet = Address::generate(&s.env);
+    order.maker = wallet.clone();
+    fund_maker(&s, &wallet, 1000);
+
+    let (making_amount, taking_amount, order_hash) =
+        s.client.fill_order(&order, &Bytes::new(&s.env), &s.taker, &100, &taker_traits());
+
+    assert!(maker_authorized_fill(&s, &wallet, &order_hash, 100));
+    assert_eq!(making_amount, 200);
+    assert_eq!(taking_amount, 100);
+    assert_eq!(s.maker_asset.balance(&wallet), 800);
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_fill_order_soroban_auth_requires_maker() {
+    let s = setup();
+    let order = build_order(&s);
+    // Enforce auth: nobody signed anything for this fill
+    s.env.set_auths(&[]);
+
+    let _ = fill_direct(&s, &order, &Bytes::new(&s.env), 100);
+}
+
+#[test]
+fn test_register_order() {
+    let s = setup();
+    let mut order = build_order(&s);
+    let wallet = Address::generate(&s.env);
+    order.maker = wallet.clone();
+    fund_maker(&s, &wallet, 1000);
+
+    let order_hash = s.client.register_order(&order);
+    assert_eq!(
+        s.env.auths(),
+        std::vec![(
+            wallet.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    s.contract_id.clone(),
+                    Symbol::new(&s.env, "register_order"),
+                    vec![&s.env, order.clone().into_val(&s.env)],
+                )),
+                sub_invocations: std::vec![],
+            }
+        )]
+    );
+    assert_eq!(order_hash, s.client.hash_order(&order));
+    assert!(s.client.is_order_registered(&order_hash));
+
+    // Later fills need neither a signature nor a fresh maker authorization of the order
+    let (making_amount, _, _) = s.client.fill_order(&order, &Bytes::new(&s.env), &s.taker, &100, &taker_traits());
+    assert_eq!(making_amount, 200);
+    assert!(!maker_authorized_fill(&s, &wallet, &order_hash, 100));
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+}
+
+#[test]
+fn test_registered_order_fills_without_maker_auth() {
+    let s = setup();
+    let mut order = build_order(&s);
+    let wallet = Address::generate(&s.env);
+    order.maker = wallet.clone();
+    fund_maker(&s, &wallet, 1000);
+    s.client.register_order(&order);
+
+    // Only the taker authorizes
+    mock_taker_fill(&s, &order, &Bytes::new(&s.env), 100, 100);
+    s.client.fill_order(&order, &Bytes::new(&s.env), &s.taker, &100, &taker_traits());
+    assert!(s.env.auths().iter().all(|(address, _)| *address != wallet));
+    assert_eq!(s.maker_asset.balance(&wallet), 800);
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+}
+
+#[test]
+fn test_unregistered_order() {
+    let s = setup();
+    let order = build_order(&s);
+    assert!(!s.client.is_order_registered(&s.client.hash_order(&order)));
+}
+
+#[test]
+fn test_bind_evm_signer() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let signer = evm_address(&s.env, &key);
+
+    assert_eq!(s.client.evm_signer_for_maker(&s.maker), None);
+    s.client.bind_evm_signer(&s.maker, &signer);
+
+    assert_eq!(
+        s.env.auths()[0].1.function,
+        AuthorizedFunction::Contract((
+            s.contract_id.clone(),
+            Symbol::new(&s.env, "bind_evm_signer"),
+            vec![&s.env, s.maker.into_val(&s.env), signer.into_val(&s.env)],
+        ))
+    );
+    assert_eq!(s.env.auths()[0].0, s.maker);
+    assert_eq!(s.client.evm_signer_for_maker(&s.maker), Some(signer));
+}
+
+#[test]
+fn test_fill_order_evm_signature() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let (order, funding) = evm_order(&s, &key);
+    s.client.bind_evm_signer(&funding, &evm_address(&s.env, &key));
+    let signature = sign_order_evm(&s, &key, &order);
+
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    assert_eq!(making_amount, 200);
+    assert_eq!(taking_amount, 100);
+    assert_eq!(s.maker_asset.balance(&funding), 800);
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+    assert_eq!(s.taker_asset.balance(&funding), 100);
+}
+
+#[test]
+fn test_fill_order_evm_signer_not_bound() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let (order, _) = evm_order(&s, &key);
+    let signature = sign_order_evm(&s, &key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::SignerNotBound)));
+}
+
+#[test]
+fn test_fill_order_evm_wrong_signer() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let other_key = k256::ecdsa::SigningKey::from_slice(&[6u8; 32]).unwrap();
+    let (order, funding) = evm_order(&s, &key);
+    s.client.bind_evm_signer(&funding, &evm_address(&s.env, &key));
+    let signature = sign_order_evm(&s, &other_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BadSignature)));
+}
+
+#[test]
+fn test_fill_order_evm_tampered_order() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let (order, funding) = evm_order(&s, &key);
+    s.client.bind_evm_signer(&funding, &evm_address(&s.env, &key));
+    let signature = sign_order_evm(&s, &key, &order);
+
+    let mut tampered = order.clone();
+    tampered.taking_amount = 1;
+
+    let result = s.client.try_fill_order(&tampered, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BadSignature)));
+}
+
+#[test]
+fn test_fill_order_evm_bad_recovery_id() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let (order, funding) = evm_order(&s, &key);
+    s.client.bind_evm_signer(&funding, &evm_address(&s.env, &key));
+    let mut signature = sign_order_evm(&s, &key, &order);
+    signature.set(64, 29);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BadSignature)));
+}
+
+#[test]
+fn test_fill_order_evm_signature_without_signer() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let (mut order, funding) = evm_order(&s, &key);
+    s.client.bind_evm_signer(&funding, &evm_address(&s.env, &key));
+    order.evm_signer = BytesN::from_array(&s.env, &[0u8; 20]);
+    let signature = sign_order_evm(&s, &key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BadSignature)));
 }
 
 #[test]
 fn test_cancel_order() {
-    let env = Env::default();
-    let contract_id = env.register_contract(None, StellarLimitOrderProtocol);
-    let client = StellarLimitOrderProtocolClient::new(&env, &contract_id);
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let order_hash = s.client.hash_order(&order);
 
-    client.initialize();
+    s.client.cancel_order(&order);
+    assert_eq!(
+        s.env.auths(),
+        std::vec![(
+            s.maker.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    s.contract_id.clone(),
+                    Symbol::new(&s.env, "cancel_order"),
+                    vec![&s.env, order.clone().into_val(&s.env)],
+                )),
+                sub_invocations: std::vec![],
+            }
+        )]
+    );
 
-    let maker = Address::generate(&env);
-    let order_hash = BytesN::from_array(&env, &[1u8; 32]);
+    // The EVM word reads as fully filled; the status tells cancellation apart
+    let remaining = s.client.remaining_invalidator_for_order(&s.maker, &order_hash);
+    assert_eq!(remaining, u128::MAX);
+    assert_eq!(s.client.order_status(&order), (OrderStatus::Cancelled, 0));
 
-    // Mock auth for maker
-    env.mock_all_auths();
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::InvalidatedOrder)));
+}
 
-    let result = client.cancel_order(&maker, &order_hash);
-    assert!(result.is_ok());
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_cancel_order_requires_maker() {
+    let s = setup();
+    let order = build_order(&s);
+    s.env.set_auths(&[]);
 
-    // Check remaining amount is 0 (cancelled)
-    let remaining = client.remaining_invalidator_for_order(&maker, &order_hash);
-    assert_eq!(remaining, 0);
+    s.env.as_contract(&s.contract_id, || {
+        let _ = StellarLimitOrderProtocol::cancel_order(&s.env, order.clone());
+    });
 }
 
 #[test]
-fn test_order_expired() {
-    let env = Env::default();
-    let contract_id = env.register_contract(None, StellarLimitOrderProtocol);
-    let client = StellarLimitOrderProtocolClient::new(&env, &contract_id);
+fn test_cancel_order_bit_invalidator() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = 300 << MakerTraits::NONCE_OR_EPOCH_OFFSET; // slot 1, bit 44
+    let signature = sign_order(&s, &s.maker_key, &order);
 
-    client.initialize();
+    s.client.cancel_order(&order);
 
-    let maker = Address::generate(&env);
-    let receiver = Address::generate(&env);
-    let maker_asset = Address::generate(&env);
-    let taker_asset = Address::generate(&env);
-    let taker = Address::generate(&env);
+    let mut expected = Word256([0u8; 32]);
+    expected.set_bit(44);
+    assert_eq!(Word256::from_u256(&s.client.bit_invalidator_for_order(&s.maker, &1)), expected);
+    assert_eq!(s.client.order_status(&order), (OrderStatus::Cancelled, 0));
 
-    let order = Order {
-        salt: env.ledger().timestamp() - 3600, // Past timestamp (expired)
-        maker: maker.clone(),
-        receiver: receiver.clone(),
-        maker_asset: maker_asset.clone(),
-        taker_asset: taker_asset.clone(),
-        making_amount: 1000,
-        taking_amount: 500,
-        maker_traits: 0,
-    };
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BitInvalidatedOrder)));
+}
 
-    let signature = Bytes::from_slice(&env, &[0u8; 64]);
-    let taker_traits = TakerTraits {
-        threshold: 1000,
-        skip_maker_permit: false,
-    };
+#[test]
+fn test_fill_consumes_nonce() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = 7 << MakerTraits::NONCE_OR_EPOCH_OFFSET;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    assert!(Word256::from_u256(&s.client.bit_invalidator_for_order(&s.maker, &0)).bit(7));
+
+    // Another order reusing the nonce is invalidated too
+    order.salt += 1;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BitInvalidatedOrder)));
+}
+
+#[test]
+fn test_bits_invalidate_for_order() {
+    let s = setup();
+    let mut mask = Word256([0u8; 32]);
+    mask.set_bit(5);
+    mask.set_bit(200);
+    s.client.bits_invalidate_for_order(&s.maker, &2, &mask.to_u256(&s.env));
+    assert_eq!(s.env.auths()[0].0, s.maker);
+
+    // Bits accumulate
+    let mut more = Word256([0u8; 32]);
+    more.set_bit(6);
+    s.client.bits_invalidate_for_order(&s.maker, &2, &more.to_u256(&s.env));
+    let stored = Word256::from_u256(&s.client.bit_invalidator_for_order(&s.maker, &2));
+    assert!(stored.bit(5) && stored.bit(6) && stored.bit(200));
+    assert!(!stored.bit(7));
+
+    let mut order = build_order(&s);
+    order.maker_traits = (2 * 256 + 6) << MakerTraits::NONCE_OR_EPOCH_OFFSET;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BitInvalidatedOrder)));
+
+    order.maker_traits = (2 * 256 + 7) << MakerTraits::NONCE_OR_EPOCH_OFFSET;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+}
+
+fn series_traits(series: u128, epoch: u128) -> u128 {
+    MakerTraits::NEED_CHECK_EPOCH_MANAGER_FLAG
+        | MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG
+        | (series << MakerTraits::SERIES_OFFSET)
+        | (epoch << MakerTraits::NONCE_OR_EPOCH_OFFSET)
+}
+
+#[test]
+fn test_increase_epoch() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = series_traits(3, 0);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    assert_eq!(s.client.epoch(&s.maker, &3), 0);
+    s.client.increase_epoch(&s.maker, &3);
+    assert_eq!(s.env.auths()[0].0, s.maker);
+    assert_eq!(s.client.epoch(&s.maker, &3), 1);
+    assert_eq!(s.client.epoch(&s.maker, &4), 0);
+
+    // Every outstanding order of the series is cancelled at once
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::WrongSeriesNonce)));
+
+    order.maker_traits = series_traits(3, 1);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+}
+
+#[test]
+fn test_epoch_manager_requires_multiple_fills() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::NEED_CHECK_EPOCH_MANAGER_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::EpochManagerAndBitInvalidatorsAreIncompatible)));
+}
+
+#[test]
+fn test_order_status_lifecycle() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let order_hash = s.client.hash_order(&order);
+
+    assert_eq!(s.client.order_status(&order), (OrderStatus::Unfilled, 1000));
+    assert_eq!(s.client.remaining_invalidator_for_order(&s.maker, &order_hash), 0);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(s.client.order_status(&order), (OrderStatus::PartiallyFilled, 800));
+    assert_eq!(s.client.remaining_invalidator_for_order(&s.maker, &order_hash), !800u128);
+
+    // Only 800 of the making amount is left: 401 taking units would buy 802, so the fill is
+    // reduced to the 800 left for 400
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &401, &taker_traits());
+    assert_eq!((making_amount, taking_amount), (800, 400));
+    assert_eq!(s.client.order_status(&order), (OrderStatus::FullyFilled, 0));
+    assert_eq!(s.client.remaining_invalidator_for_order(&s.maker, &order_hash), u128::MAX);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &1, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::InvalidatedOrder)));
+}
+
+#[test]
+fn test_order_status_bit_invalidator() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = 9 << MakerTraits::NONCE_OR_EPOCH_OFFSET;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.post_order(&order, &Bytes::new(&s.env), &signature, &1_000);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    // A partial fill uses the nonce, so nothing is left to fill and the order is delisted
+    assert_eq!(s.client.order_status(&order), (OrderStatus::FullyFilled, 0));
+    assert_eq!(s.client.orders_by_maker(&s.maker, &0, &10).orders.len(), 0);
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BitInvalidatedOrder)));
+
+    // An order sharing the nonce was never filled itself but can no longer be
+    let mut sibling = order.clone();
+    sibling.salt += 1;
+    assert_eq!(s.client.order_status(&sibling), (OrderStatus::Cancelled, 0));
+
+    // Same through a direct nonce invalidation
+    let mut mask = Word256([0u8; 32]);
+    mask.set_bit(10);
+    s.client.bits_invalidate_for_order(&s.maker, &0, &mask.to_u256(&s.env));
+    order.maker_traits = 10 << MakerTraits::NONCE_OR_EPOCH_OFFSET;
+    assert_eq!(s.client.order_status(&order), (OrderStatus::Cancelled, 0));
+}
 
-    let result = client.fill_order(&order, &signature, &taker, &100, &taker_traits);
-    
-    // Should fail due to expired order
-    assert!(result.is_err());
-} 
\ No newline at end of file
+#[test]
+fn test_fill_order_zero_making_amount() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.making_amount = 1;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // 100 * 1 / 500 rounds down to nothing
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::SwapWithZeroAmount)));
+}
+
+#[test]
+fn test_order_expired() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 10_000);
+
+    let mut order = build_order(&s);
+    order.maker_traits = ((10_000 - 3600) as u128) << MakerTraits::EXPIRATION_OFFSET; // Past expiration
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::OrderExpired)));
+}
+
+#[test]
+fn test_order_not_yet_expired() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 10_000);
+
+    let mut order = build_order(&s);
+    order.maker_traits = 10_000u128 << MakerTraits::EXPIRATION_OFFSET; // Valid through this second
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let (making_amount, _, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(making_amount, 200);
+}
+
+/// Allowed-sender bits for `address`: the last four bytes of its 32-byte payload
+fn allowed_sender_bits(env: &Env, address: &Address) -> u128 {
+    let xdr = address.clone().to_xdr(env);
+    let mut tail = [0u8; 4];
+    xdr.slice(xdr.len() - 4..).copy_into_slice(&mut tail);
+    u32::from_be_bytes(tail) as u128
+}
+
+#[test]
+fn test_maker_traits_decoding() {
+    let traits = MakerTraits(
+        MakerTraits::NO_PARTIAL_FILLS_FLAG
+            | MakerTraits::PRE_INTERACTION_CALL_FLAG
+            | MakerTraits::UNWRAP_NATIVE_FLAG
+            | (0xBEEF << MakerTraits::SERIES_OFFSET)
+            | (42 << MakerTraits::NONCE_OR_EPOCH_OFFSET)
+            | (1_700_000_000 << MakerTraits::EXPIRATION_OFFSET)
+            | 0x0102_0304,
+    );
+
+    assert!(!traits.allow_partial_fills());
+    assert!(!traits.allow_multiple_fills());
+    assert!(traits.use_bit_invalidator());
+    assert!(traits.need_pre_interaction_call());
+    assert!(!traits.need_post_interaction_call());
+    assert!(!traits.need_check_epoch_manager());
+    assert!(!traits.has_extension());
+    assert!(traits.unwrap_native());
+    assert_eq!(traits.series(), 0xBEEF);
+    assert_eq!(traits.nonce_or_epoch(), 42);
+    assert_eq!(traits.expiration_time(), 1_700_000_000);
+    assert!(!traits.is_expired(1_700_000_000));
+    assert!(traits.is_expired(1_700_000_001));
+
+    let mut sender = [0xAAu8; 32];
+    assert!(!traits.is_allowed_sender(&sender));
+    sender[28..].copy_from_slice(&[1, 2, 3, 4]);
+    assert!(traits.is_allowed_sender(&sender));
+
+    // Zeroed fields: public, never expires, partial and single fill
+    let public = MakerTraits(0);
+    assert!(public.is_allowed_sender(&[0xAAu8; 32]));
+    assert!(!public.is_expired(u64::MAX));
+    assert!(public.allow_partial_fills());
+    assert!(public.use_bit_invalidator());
+}
+
+#[test]
+fn test_private_order() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = allowed_sender_bits(&s.env, &s.taker);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // Another taker is rejected
+    let result = s.client.try_fill_order(&order, &signature, &Address::generate(&s.env), &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::PrivateOrder)));
+
+    let (making_amount, _, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(making_amount, 200);
+}
+
+#[test]
+fn test_allowed_sender_compares_full_address() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = allowed_sender_bits(&s.env, &s.taker);
+    order.allowed_sender = s.taker.clone();
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // A ground address sharing the taker's low bits passes the filter, not the allowed sender
+    let mut xdr = s.taker.clone().to_xdr(&s.env);
+    xdr.set(10, xdr.get(10).unwrap() ^ 0xFF);
+    let impostor = Address::from_xdr(&s.env, &xdr).unwrap();
+    assert_eq!(allowed_sender_bits(&s.env, &impostor), order.maker_traits);
+    let result = s.client.try_fill_order(&order, &signature, &impostor, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::PrivateOrder)));
+
+    let (making_amount, _, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(making_amount, 200);
+}
+
+/// Whether `taker` authorized the `fill_order` call itself
+fn taker_authorized_fill(s: &Setup, taker: &Address) -> bool {
+    s.env.auths().iter().any(|(address, invocation)| {
+        address == taker
+            && matches!(&invocation.function, AuthorizedFunction::Contract((contract, function, _))
+                if *contract == s.contract_id && *function == Symbol::new(&s.env, "fill_order"))
+    })
+}
+
+#[test]
+fn test_allowed_sender() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    order.allowed_sender = s.taker.clone();
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &Address::generate(&s.env), &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::PrivateOrder)));
+
+    let (making_amount, _, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(making_amount, 200);
+    assert!(taker_authorized_fill(&s, &s.taker));
+
+    // Public orders are authorized by their taker all the same
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert!(taker_authorized_fill(&s, &s.taker));
+}
+
+#[test]
+fn test_any_sender_hashes_as_zero() {
+    let s = setup();
+    let any_sender = s.client.any_sender();
+    assert_eq!(any_sender.to_string(), String::from_str(&s.env, "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF"));
+    let bytes = s.env.as_contract(&s.contract_id, || StellarLimitOrderProtocol::address_to_bytes(&s.env, &any_sender));
+    assert_eq!(bytes, Bytes::from_array(&s.env, &[0u8; 32]));
+}
+
+#[test]
+fn test_no_partial_fills() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::NO_PARTIAL_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::PartialFillNotAllowed)));
+
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &500, &taker_traits());
+    assert_eq!((making_amount, taking_amount), (1000, 500));
+}
+
+#[test]
+fn test_single_fill_order() {
+    let s = setup();
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    // Without ALLOW_MULTIPLE_FILLS the first fill consumes the order
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::BitInvalidatedOrder)));
+}
+
+#[test]
+fn test_allow_multiple_fills() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    assert_eq!(s.maker_asset.balance(&s.taker), 400);
+    assert_eq!(s.taker_asset.balance(&s.maker), 200);
+}
+
+#[test]
+fn test_taker_traits_decoding() {
+    let env = Env::default();
+
+    let mut word = Word256([0u8; 32]);
+    word.set_bit(TakerTraits::MAKER_AMOUNT_FLAG);
+    word.set_bit(TakerTraits::SKIP_ORDER_PERMIT_FLAG);
+    word.set_bit(TakerTraits::ARGS_HAS_TARGET);
+    word.0[1..4].copy_from_slice(&[0x00, 0x01, 0x02]); // extension length, bits 224..248
+    word.0[4..7].copy_from_slice(&[0x00, 0x00, 0x2c]); // interaction length, bits 200..224
+    word.0[16..].copy_from_slice(&123_456u128.to_be_bytes());
+
+    // Bit 255 set: the EVM uint256 arrives as a negative I256
+    let value = word.to_i256(&env);
+    assert!(value.to_i128().is_none());
+    assert_eq!(Word256::from_i256(&value), word);
+
+    let traits = TakerTraits::from_i256(&value);
+    assert!(traits.maker_amount);
+    assert!(!traits.unwrap_native);
+    assert!(traits.skip_maker_permit);
+    assert!(traits.args_has_target);
+    assert_eq!(traits.args_extension_length, 0x0102);
+    assert_eq!(traits.args_interaction_length, 0x2c);
+    assert_eq!(traits.threshold, 123_456);
+
+    // Bits between the threshold and the interaction length are ignored
+    let mut word = Word256([0u8; 32]);
+    word.set_bit(TakerTraits::UNWRAP_NATIVE_FLAG);
+    word.set_bit(190);
+    let traits = TakerTraits::from_i256(&word.to_i256(&env));
+    assert!(traits.unwrap_native);
+    assert!(!traits.maker_amount);
+    assert_eq!(traits.threshold, 0);
+
+    // Thresholds wider than u128 saturate
+    let mut word = Word256([0u8; 32]);
+    word.set_bit(184);
+    assert_eq!(TakerTraits::from_i256(&word.to_i256(&env)).threshold, u128::MAX);
+
+    assert_eq!(TakerTraits::from_i256(&I256::from_i32(&env, 0)), taker_traits());
+}
+
+#[test]
+fn test_threshold_taking_mode() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // Taker pays 100 and wants at least 201 of the maker asset; the order gives 200
+    let mut traits = taker_traits();
+    traits.threshold = 201;
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &traits);
+    assert_eq!(result, Err(Ok(Error::MakingAmountTooLow)));
+
+    traits.threshold = 200;
+    let (making_amount, _, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &traits);
+    assert_eq!(making_amount, 200);
+}
+
+#[test]
+fn test_threshold_making_mode() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // Taker buys 200 of the maker asset and is willing to pay at most 99; the order asks 100
+    let mut traits = taker_traits();
+    traits.maker_amount = true;
+    traits.threshold = 99;
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &200, &traits);
+    assert_eq!(result, Err(Ok(Error::TakingAmountTooHigh)));
+
+    traits.threshold = 100;
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &200, &traits);
+    assert_eq!((making_amount, taking_amount), (200, 100));
+}
+
+fn resolver_order(s: &Setup) -> ResolverOrder {
+    ResolverOrder {
+        salt: 12345,
+        maker: s.maker.clone(),
+        receiver: s.maker.clone(),
+        maker_asset: s.maker_asset.address.clone(),
+        taker_asset: s.taker_asset.address.clone(),
+        making_amount: 1000,
+        taking_amount: 500,
+        maker_traits: 0,
+    }
+}
+
+/// LOP order that `fill_args` derives from `resolver_order` under a signature of `signature_len` bytes
+fn converted_order(s: &Setup, resolver_order: &ResolverOrder, signature_len: usize) -> Order {
+    let signature = Bytes::from_slice(&s.env, &[0u8; 65][..signature_len]);
+    s.env.as_contract(&s.contract_id, || {
+        StellarLimitOrderProtocol::convert_resolver_order(&s.env, resolver_order.clone(), &signature).unwrap()
+    })
+}
+
+/// Maker signature over the LOP order that `fill_args` derives from `resolver_order`
+fn sign_resolver_order(s: &Setup, resolver_order: &ResolverOrder) -> Bytes {
+    sign_order(s, &s.maker_key, &converted_order(s, resolver_order, 64))
+}
+
+/// `args` as built by the resolver: length-prefixed target XDR, then the factory's data
+fn args_with_target(env: &Env, target: &Address, rest: &[u8]) -> Bytes {
+    let target_xdr = target.clone().to_xdr(env);
+    let mut args = Bytes::from_array(env, &target_xdr.len().to_be_bytes());
+    args.append(&target_xdr);
+    args.append(&Bytes::from_slice(env, rest));
+    args
+}
+
+fn args_has_target(env: &Env) -> I256 {
+    let mut word = Word256([0u8; 32]);
+    word.set_bit(TakerTraits::ARGS_HAS_TARGET);
+    word.to_i256(env)
+}
+
+#[test]
+fn test_fill_args_sends_making_amount_to_target() {
+    let s = setup();
+    let order = resolver_order(&s);
+    let signature = sign_resolver_order(&s, &order);
+    let escrow = Address::generate(&s.env);
+
+    let args = args_with_target(&s.env, &escrow, &[9, 9, 9]);
+    s.client.fill_args(&order, &signature, &s.taker, &100, &args_has_target(&s.env), &args);
+
+    assert_eq!(s.maker_asset.balance(&escrow), 200);
+    assert_eq!(s.maker_asset.balance(&s.maker), 1_000_000 - 200);
+    assert_eq!(s.taker_asset.balance(&s.maker), 100);
+    assert_eq!(s.taker_asset.balance(&s.taker), 1_000_000 - 100);
+}
+
+#[test]
+fn test_fill_args_stellar_signed_after_binding_evm_key() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    s.client.bind_evm_signer(&s.maker, &evm_address(&s.env, &key));
+
+    // The maker's ed25519 signature still covers an order without an EVM signer
+    let order = resolver_order(&s);
+    assert_eq!(converted_order(&s, &order, 64).evm_signer, BytesN::from_array(&s.env, &[0u8; 20]));
+    let signature = sign_resolver_order(&s, &order);
+    s.client.fill_args(&order, &signature, &s.taker, &100, &I256::from_i32(&s.env, 0), &Bytes::new(&s.env));
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+}
+
+#[test]
+fn test_fill_args_evm_signed() {
+    let s = setup();
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let (_, funding) = evm_order(&s, &key);
+    s.client.bind_evm_signer(&funding, &evm_address(&s.env, &key));
+
+    let mut order = resolver_order(&s);
+    order.maker = funding.clone();
+    order.receiver = funding.clone();
+    let signature = sign_order_evm(&s, &key, &converted_order(&s, &order, 65));
+    s.client.fill_args(&order, &signature, &s.taker, &100, &I256::from_i32(&s.env, 0), &Bytes::new(&s.env));
+    assert_eq!(s.maker_asset.balance(&funding), 800);
+}
+
+#[test]
+fn test_fill_args_passes_remaining_args_to_factory() {
+    let s = setup();
+    let factory = s.env.register_contract(None, TestFactory);
+    s.client.initialize(&factory);
+
+    let mut order = resolver_order(&s);
+    order.maker_traits = MakerTraits::POST_INTERACTION_CALL_FLAG;
+    let signature = sign_resolver_order(&s, &order);
+    let escrow = Address::generate(&s.env);
+
+    let args = args_with_target(&s.env, &escrow, &[1, 2, 3, 4]);
+    s.client.fill_args(&order, &signature, &s.taker, &100, &args_has_target(&s.env), &args);
+
+    assert_eq!(s.maker_asset.balance(&escrow), 200);
+    s.env.as_contract(&factory, || {
+        let extra: Bytes = s.env.storage().instance().get(&symbol_short!("extra")).unwrap();
+        assert_eq!(extra, Bytes::from_array(&s.env, &[1, 2, 3, 4]));
+        let amounts: (u128, u128) = s.env.storage().instance().get(&symbol_short!("amounts")).unwrap();
+        assert_eq!(amounts, (200, 100));
+        let factory_order: FactoryOrder = s.env.storage().instance().get(&symbol_short!("order")).unwrap();
+        assert_eq!(factory_order.maker_traits, MakerTraits::POST_INTERACTION_CALL_FLAG);
+    });
+}
+
+#[test]
+fn test_fill_args_without_target() {
+    let s = setup();
+    let order = resolver_order(&s);
+    let signature = sign_resolver_order(&s, &order);
+
+    // Without _ARGS_HAS_TARGET the args are not parsed and the taker receives the making amount
+    s.client.fill_args(&order, &signature, &s.taker, &100, &I256::from_i32(&s.env, 0), &Bytes::from_array(&s.env, &[0xff; 8]));
+
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+}
+
+#[test]
+fn test_fill_args_truncated_target() {
+    let s = setup();
+    let order = resolver_order(&s);
+    let signature = sign_resolver_order(&s, &order);
+
+    let args = args_with_target(&s.env, &Address::generate(&s.env), &[]);
+    let truncated = args.slice(0..args.len() - 1);
+    let result = s.client.try_fill_args(&order, &signature, &s.taker, &100, &args_has_target(&s.env), &truncated);
+    assert_eq!(result, Err(Ok(Error::InvalidArgs)));
+
+    let result = s.client.try_fill_args(&order, &signature, &s.taker, &100, &args_has_target(&s.env), &Bytes::from_array(&s.env, &[0, 0]));
+    assert_eq!(result, Err(Ok(Error::InvalidArgs)));
+}
+
+#[test]
+fn test_remaining_amount_persistent_ttl() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let (_, _, order_hash) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    // Still live well past the default entry TTL, short of the extended one
+    let sequence = s.env.ledger().sequence();
+    s.env.ledger().with_mut(|li| li.sequence_number = sequence + StellarLimitOrderProtocol::PERSISTENT_TTL_EXTEND_TO - 1);
+    s.env.as_contract(&s.contract_id, || {
+        let key = DataKey::RemainingInvalidator(order_hash.clone());
+        assert_eq!(s.env.storage().persistent().get(&key), Some(RemainingInvalidator::remains(800)));
+    });
+}
+
+/// Lay out the dynamic fields in `interactions` and record their end offsets
+fn with_extension(order: &mut Order, fields: &[(DynamicField, Bytes)]) {
+    let env = order.interactions.env().clone();
+    order.offsets = 0;
+    order.interactions = Bytes::new(&env);
+    for index in 0..DynamicField::COUNT {
+        for (field, data) in fields {
+            if *field as u32 == index {
+                order.interactions.append(data);
+            }
+        }
+        order.offsets |= (order.interactions.len() as u128) << (index * DynamicField::OFFSET_BITS);
+    }
+}
+
+/// Put `predicate` in the order's predicate field
+fn with_predicate(order: &mut Order, predicate: &Bytes) {
+    with_extension(order, &[(DynamicField::Predicate, predicate.clone())]);
+}
+
+fn timestamp_below(env: &Env, timestamp: u64) -> Bytes {
+    let mut predicate = Bytes::from_array(env, &[predicate::TIMESTAMP_BELOW]);
+    predicate.extend_from_array(&timestamp.to_be_bytes());
+    predicate
+}
+
+/// Append an XDR value with its u32 length prefix
+fn append_xdr(predicate: &mut Bytes, xdr: &Bytes) {
+    predicate.extend_from_array(&xdr.len().to_be_bytes());
+    predicate.append(xdr);
+}
+
+/// `op` comparing `oracle.price()` against `value`
+fn price_compare(env: &Env, op: u8, value: i128, oracle: &Address) -> Bytes {
+    let mut predicate = Bytes::from_array(env, &[op]);
+    predicate.extend_from_array(&value.to_be_bytes());
+    append_xdr(&mut predicate, &oracle.clone().to_xdr(env));
+    append_xdr(&mut predicate, &symbol_short!("price").to_xdr(env));
+    append_xdr(&mut predicate, &Vec::<soroban_sdk::Val>::new(env).to_xdr(env));
+    predicate
+}
+
+fn combine(env: &Env, op: u8, operands: &[Bytes]) -> Bytes {
+    let mut predicate = Bytes::from_array(env, &[op, operands.len() as u8]);
+    for operand in operands {
+        predicate.append(operand);
+    }
+    predicate
+}
+
+fn predicate_fill(s: &Setup, predicate: &Bytes) -> Result<(u128, u128, BytesN<32>), Result<Error, soroban_sdk::InvokeError>> {
+    let mut order = build_order(s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    with_predicate(&mut order, predicate);
+    let signature = sign_order(s, &s.maker_key, &order);
+    s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits()).map(|result| result.unwrap())
+}
+
+#[test]
+fn test_predicate_timestamp_below() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 10_000);
+
+    assert!(predicate_fill(&s, &timestamp_below(&s.env, 10_001)).is_ok());
+    assert_eq!(predicate_fill(&s, &timestamp_below(&s.env, 10_000)), Err(Ok(Error::PredicateIsNotTrue)));
+}
+
+#[test]
+fn test_predicate_logic() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 10_000);
+    let (yes, no) = (timestamp_below(&s.env, 20_000), timestamp_below(&s.env, 5_000));
+    let not = |operand: &Bytes| {
+        let mut predicate = Bytes::from_array(&s.env, &[predicate::NOT]);
+        predicate.append(operand);
+        predicate
+    };
+
+    assert!(predicate_fill(&s, &combine(&s.env, predicate::AND, &[yes.clone(), yes.clone()])).is_ok());
+    assert!(predicate_fill(&s, &combine(&s.env, predicate::OR, &[no.clone(), yes.clone()])).is_ok());
+    assert!(predicate_fill(&s, &not(&no)).is_ok());
+    assert!(predicate_fill(&s, &combine(&s.env, predicate::AND, &[yes.clone(), not(&combine(&s.env, predicate::OR, &[no.clone(), no.clone()]))])).is_ok());
+
+    let not_true = Err(Ok(Error::PredicateIsNotTrue));
+    assert_eq!(predicate_fill(&s, &combine(&s.env, predicate::AND, &[yes.clone(), no.clone()])), not_true);
+    assert_eq!(predicate_fill(&s, &combine(&s.env, predicate::OR, &[no.clone(), no.clone()])), not_true);
+    assert_eq!(predicate_fill(&s, &not(&yes)), not_true);
+    assert_eq!(predicate_fill(&s, &combine(&s.env, predicate::OR, &[])), not_true);
+}
+
+#[test]
+fn test_predicate_contract_calls() {
+    let s = setup();
+    let oracle = s.env.register_contract(None, TestOracle);
+    TestOracleClient::new(&s.env, &oracle).set_price(&1_500);
+
+    assert!(predicate_fill(&s, &price_compare(&s.env, predicate::LT, 2_000, &oracle)).is_ok());
+    assert!(predicate_fill(&s, &price_compare(&s.env, predicate::GT, 1_000, &oracle)).is_ok());
+    assert!(predicate_fill(&s, &price_compare(&s.env, predicate::EQ, 1_500, &oracle)).is_ok());
+
+    let not_true = Err(Ok(Error::PredicateIsNotTrue));
+    assert_eq!(predicate_fill(&s, &price_compare(&s.env, predicate::LT, 1_500, &oracle)), not_true);
+    assert_eq!(predicate_fill(&s, &price_compare(&s.env, predicate::GT, 1_500, &oracle)), not_true);
+    assert_eq!(predicate_fill(&s, &price_compare(&s.env, predicate::EQ, -1_500, &oracle)), not_true);
+}
+
+#[test]
+fn test_predicate_short_circuits() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 10_000);
+    // A call to a function that doesn't exist fails the predicate only when it is evaluated
+    let missing = price_compare(&s.env, predicate::EQ, 0, &s.maker_asset.address);
+
+    let result = predicate_fill(&s, &combine(&s.env, predicate::OR, &[timestamp_below(&s.env, 20_000), missing.clone()]));
+    assert!(result.is_ok());
+    let result = predicate_fill(&s, &combine(&s.env, predicate::AND, &[timestamp_below(&s.env, 20_000), missing]));
+    assert_eq!(result, Err(Ok(Error::InvalidPredicate)));
+}
+
+#[test]
+fn test_predicate_epoch_equals() {
+    let s = setup();
+    let mut predicate = Bytes::from_array(&s.env, &[predicate::EPOCH_EQUALS]);
+    predicate.extend_from_array(&3u64.to_be_bytes());
+    predicate.extend_from_array(&1u64.to_be_bytes());
+
+    assert_eq!(predicate_fill(&s, &predicate), Err(Ok(Error::PredicateIsNotTrue)));
+    s.client.increase_epoch(&s.maker, &3);
+    assert!(predicate_fill(&s, &predicate).is_ok());
+}
+
+#[test]
+fn test_predicate_malformed() {
+    let s = setup();
+    let invalid = Err(Ok(Error::InvalidPredicate));
+
+    // Unknown tag, truncated operand, trailing bytes
+    assert_eq!(predicate_fill(&s, &Bytes::from_array(&s.env, &[0xff])), invalid);
+    assert_eq!(predicate_fill(&s, &Bytes::from_array(&s.env, &[predicate::TIMESTAMP_BELOW, 0, 0])), invalid);
+    let mut trailing = timestamp_below(&s.env, u64::MAX);
+    trailing.push_back(0);
+    assert_eq!(predicate_fill(&s, &trailing), invalid);
+
+    // Nesting past the depth limit
+    let mut nested = Bytes::new(&s.env);
+    for _ in 0..20 {
+        nested.push_back(predicate::NOT);
+    }
+    nested.append(&timestamp_below(&s.env, u64::MAX));
+    assert_eq!(predicate_fill(&s, &nested), invalid);
+}
+
+#[test]
+fn test_predicate_offsets_out_of_bounds() {
+    let s = setup();
+    let mut order = build_order(&s);
+    with_predicate(&mut order, &timestamp_below(&s.env, u64::MAX));
+    order.interactions = Bytes::from_array(&s.env, &[predicate::TIMESTAMP_BELOW]);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::InvalidExtension)));
+}
+
+#[test]
+fn test_extension_encoding() {
+    let s = setup();
+    let mut order = build_order(&s);
+    assert!(extension::encode(&s.env, &order).is_empty());
+
+    with_extension(&mut order, &[(DynamicField::Predicate, timestamp_below(&s.env, 10)), (DynamicField::FeeData, Bytes::from_array(&s.env, &[1, 2]))]);
+    order.interactions.extend_from_array(&[3]); // custom data
+    let encoded = extension::encode(&s.env, &order);
+    assert_eq!(encoded.len(), extension::OFFSETS_LEN + order.interactions.len());
+    assert_eq!(extension::decode(&encoded), Ok((order.offsets, order.interactions.clone())));
+
+    // Too short for the offsets word, fields past the end, fields out of order
+    assert_eq!(extension::decode(&encoded.slice(..extension::OFFSETS_LEN - 1)), Err(Error::InvalidExtension));
+    assert_eq!(extension::decode(&encoded.slice(..encoded.len() - 2)), Err(Error::InvalidExtension));
+    let mut swapped = Bytes::from_array(&s.env, &(9u128 | 4 << DynamicField::OFFSET_BITS).to_be_bytes());
+    swapped.append(&order.interactions);
+    assert_eq!(extension::decode(&swapped), Err(Error::InvalidExtension));
+}
+
+#[test]
+fn test_inline_extension_offsets_checked() {
+    let s = setup();
+    let mut order = build_order(&s);
+    with_predicate(&mut order, &timestamp_below(&s.env, u64::MAX));
+    // The predicate still fits, but the field before it ends past it
+    order.offsets |= 0xFFFF;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::InvalidExtension)));
+}
+
+/// Order leaving its extension, a predicate, to the taker's args
+fn args_extension_order(s: &Setup, timestamp: u64) -> (Order, Bytes) {
+    let mut extended = build_order(s);
+    with_predicate(&mut extended, &timestamp_below(&s.env, timestamp));
+    let extension = extension::encode(&s.env, &extended);
+    let mut order = build_order(s);
+    order.maker_traits = MakerTraits::HAS_EXTENSION_FLAG | MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    (order, extension)
+}
+
+/// The order with its extension attached, as it is hashed and signed
+fn attached(order: &Order, extension: &Bytes) -> Order {
+    extension::attach(order.clone(), extension).unwrap()
+}
+
+fn fill_with_extension(s: &Setup, order: &Order, signature: &Bytes, extension: &Bytes) -> Result<(u128, u128, BytesN<32>), Result<Error, soroban_sdk::InvokeError>> {
+    let traits = TakerTraits { args_extension_length: extension.len(), ..taker_traits() };
+    s.client.try_fill_order_args(order, signature, &s.taker, &100, &traits, extension).map(|result| result.unwrap())
+}
+
+#[test]
+fn test_args_extension_committed_by_hash() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 1_000);
+    let (order, extension) = args_extension_order(&s, 2_000);
+    let signature = sign_order(&s, &s.maker_key, &attached(&order, &extension));
+    let (_, _, order_hash) = fill_with_extension(&s, &order, &signature, &extension).unwrap();
+    assert_eq!(order_hash, s.client.hash_order(&attached(&order, &extension)));
+
+    // Passed with its extension attached, the order needs no args
+    assert_eq!(fill_with_extension(&s, &attached(&order, &extension), &signature, &Bytes::new(&s.env)).unwrap().2, order_hash);
+
+    // The extension applies: its predicate fails once the time passes
+    s.env.ledger().with_mut(|li| li.timestamp = 3_000);
+    assert_eq!(fill_with_extension(&s, &order, &signature, &extension), Err(Ok(Error::PredicateIsNotTrue)));
+
+    // Another extension changes the order hash, so the maker's signature no longer holds
+    let (_, other) = args_extension_order(&s, 4_000);
+    assert_ne!(s.client.hash_order(&attached(&order, &other)), order_hash);
+    let key = k256::ecdsa::SigningKey::from_slice(&[5u8; 32]).unwrap();
+    let (mut evm, funding) = evm_order(&s, &key);
+    s.client.bind_evm_signer(&funding, &evm_address(&s.env, &key));
+    evm.maker_traits = order.maker_traits;
+    let evm_signature = sign_order_evm(&s, &key, &attached(&evm, &extension));
+    assert_eq!(fill_with_extension(&s, &evm, &evm_signature, &other), Err(Ok(Error::BadSignature)));
+}
+
+#[test]
+fn test_args_extension_rejected() {
+    let s = setup();
+    let (order, extension) = args_extension_order(&s, u64::MAX);
+    let signature = sign_order(&s, &s.maker_key, &attached(&order, &extension));
+
+    // Without HAS_EXTENSION
+    let mut plain = order.clone();
+    plain.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    assert_eq!(fill_with_extension(&s, &plain, &signature, &extension), Err(Ok(Error::UnexpectedOrderExtension)));
+
+    // Alongside an inline extension
+    let mut inline = order.clone();
+    with_predicate(&mut inline, &timestamp_below(&s.env, u64::MAX));
+    assert_eq!(fill_with_extension(&s, &inline, &signature, &extension), Err(Ok(Error::InvalidExtension)));
+
+    // Malformed
+    let truncated = extension.slice(..extension.len() - 1);
+    assert_eq!(fill_with_extension(&s, &order, &signature, &truncated), Err(Ok(Error::InvalidExtension)));
+}
+
+#[test]
+fn test_args_extension_quoted_and_posted() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 1_000);
+    let (order, extension) = args_extension_order(&s, 2_000);
+    let signature = sign_order(&s, &s.maker_key, &attached(&order, &extension));
+
+    let traits = TakerTraits { args_extension_length: extension.len(), ..taker_traits() };
+    let quote = s.client.quote_fill(&order, &signature, &s.taker, &100, &traits, &extension);
+    assert_eq!((quote.making_amount, quote.order_hash.clone()), (200, s.client.hash_order(&attached(&order, &extension))));
+    assert_eq!(s.client.quote_at(&order, &extension, &200, &1_000), 100);
+    let (_, other) = args_extension_order(&s, 4_000);
+    let mut plain = order.clone();
+    plain.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    assert_eq!(s.client.try_quote_at(&plain, &other, &200, &1_000), Err(Ok(Error::UnexpectedOrderExtension)));
+
+    // Listed with its extension, and delisted once cancelled with it attached
+    let order_hash = s.client.post_order(&order, &extension, &signature, &1_000);
+    assert_eq!(order_hash, quote.order_hash);
+    let posted = s.client.posted_order(&order_hash).unwrap();
+    assert_eq!((posted.order, posted.extension), (order.clone(), extension.clone()));
+    let listed = s.client.orders_by_maker(&s.maker, &0, &10).orders;
+    assert_eq!((listed.len(), listed.get(0).unwrap().extension), (1, extension.clone()));
+    s.client.cancel_order(&attached(&order, &extension));
+    assert_eq!(s.client.orders_by_maker(&s.maker, &0, &10).orders.len(), 0);
+}
+
+/// Auction details in the EVM layout; `points` are (rate bump, delay) pairs
+fn auction_details(env: &Env, gas: (u32, u32), start_time: u32, duration: u32, initial_rate_bump: u32, points: &[(u32, u16)]) -> Bytes {
+    let mut details = Bytes::new(env);
+    details.extend_from_slice(&gas.0.to_be_bytes()[1..]);
+    details.extend_from_array(&gas.1.to_be_bytes());
+    details.extend_from_array(&start_time.to_be_bytes());
+    details.extend_from_slice(&duration.to_be_bytes()[1..]);
+    details.extend_from_slice(&initial_rate_bump.to_be_bytes()[1..]);
+    for (rate_bump, delay) in points {
+        details.extend_from_slice(&rate_bump.to_be_bytes()[1..]);
+        details.extend_from_array(&delay.to_be_bytes());
+    }
+    details
+}
+
+/// Order whose price starts 10% above the signed one at t=1000, is 5% above at t=1200 and
+/// reaches it at t=2000
+fn auction_order(s: &Setup, gas: (u32, u32)) -> Order {
+    let mut order = build_order(s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let details = auction_details(&s.env, gas, 1_000, 1_000, 1_000_000, &[(500_000, 200)]);
+    with_extension(&mut order, &[(DynamicField::MakingAmountData, details.clone()), (DynamicField::TakingAmountData, details)]);
+    order
+}
+
+#[test]
+fn test_auction_quote_follows_curve() {
+    let s = setup();
+    let order = auction_order(&s, (0, 0));
+
+    // 1000 making at the signed price costs 500
+    let quotes = [(0, 550), (1_000, 550), (1_100, 538), (1_200, 525), (1_600, 513), (2_000, 500), (5_000, 500)];
+    for (timestamp, taking_amount) in quotes {
+        assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &1_000, &timestamp), taking_amount, "at {timestamp}");
+    }
+}
+
+#[test]
+fn test_auction_gas_bump() {
+    let s = setup();
+    let order = auction_order(&s, (200_000, 1));
+
+    assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &1_000, &1_000), 540);
+    assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &1_000, &1_600), 503); // 2.5% - 2%
+    assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &1_000, &1_900), 500); // bump can't go negative
+
+    // Without a gas price estimate the gas bump is ignored
+    let order = auction_order(&s, (200_000, 0));
+    assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &1_000, &1_000), 550);
+}
+
+#[test]
+fn test_auction_fill_uses_ledger_time() {
+    let s = setup();
+    let order = auction_order(&s, (0, 0));
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // 7.5% bump: 100 * 1000 / 500 / 1.075
+    s.env.ledger().with_mut(|li| li.timestamp = 1_100);
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!((making_amount, taking_amount), (186, 100));
+
+    s.env.ledger().with_mut(|li| li.timestamp = 2_000);
+    let (making_amount, _, _) = s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(making_amount, 200);
+}
+
+#[test]
+fn test_auction_malformed_details() {
+    let s = setup();
+    let mut order = build_order(&s);
+    let details = auction_details(&s.env, (0, 0), 1_000, 1_000, 1_000_000, &[(500_000, 200)]);
+    with_extension(&mut order, &[(DynamicField::TakingAmountData, details.slice(..details.len() - 1))]);
+
+    assert_eq!(s.client.try_quote_at(&order, &Bytes::new(&s.env), &1_000, &1_100), Err(Ok(Error::InvalidExtension)));
+}
+
+#[test]
+fn test_quote_without_auction() {
+    let s = setup();
+    let mut order = build_order(&s);
+    assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &3, &0), 2); // 1.5 rounded up for the maker
+
+    // 256-bit intermediates: u128::MAX * u128::MAX doesn't overflow
+    order.making_amount = u128::MAX;
+    order.taking_amount = u128::MAX;
+    assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &u128::MAX, &0), u128::MAX);
+}
+
+#[test]
+fn test_making_mode_fill() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let mut traits = taker_traits();
+    traits.maker_amount = true;
+
+    // 3 of the maker asset cost 1.5, rounded up for the maker
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &3, &traits);
+    assert_eq!((making_amount, taking_amount), (3, 2));
+    assert_eq!(s.client.order_status(&order), (OrderStatus::PartiallyFilled, 997));
+
+    // Asking for more than is left buys what is left
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &5_000, &traits);
+    assert_eq!((making_amount, taking_amount), (997, 499));
+    assert_eq!(s.maker_asset.balance(&s.taker), 1_000);
+    assert_eq!(s.taker_asset.balance(&s.maker), 501);
+}
+
+#[test]
+fn test_threshold_applies_pro_rata_to_reduced_fill() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.client.fill_order(&order, &signature, &s.taker, &400, &taker_traits());
+
+    // 200 left; the taker asks for 1000 paying at most 499, a price the 200 at 100 beats
+    let mut traits = taker_traits();
+    traits.maker_amount = true;
+    traits.threshold = 499;
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &1_000, &traits);
+    assert_eq!(result, Err(Ok(Error::TakingAmountTooHigh)));
+    traits.threshold = 500;
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &1_000, &traits);
+    assert_eq!((making_amount, taking_amount), (200, 100));
+}
+
+#[test]
+fn test_fill_amounts_do_not_overflow() {
+    let s = setup();
+    let balance = i128::MAX - s.maker_asset.balance(&s.maker);
+    s.maker_asset.mint(&s.maker, &balance);
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    order.making_amount = balance as u128;
+    order.taking_amount = 5;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // amount * making_amount is past u128 for amounts of 3 and up
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &2, &taker_traits());
+    assert_eq!((making_amount, taking_amount), (balance as u128 * 2 / 5, 2));
+    let (making_amount, _, _) = s.client.fill_order(&order, &signature, &s.taker, &3, &taker_traits());
+    let balance = balance as u128;
+    assert_eq!(making_amount, balance / 5 * 3 + balance % 5 * 3 / 5);
+}
+
+/// Deterministic pseudo-random numbers for the property tests (xorshift64)
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u128) -> u128 {
+        (self.next() as u128) % bound
+    }
+}
+
+/// Fill random orders with random partial fills in both modes until they are used up: the
+/// taker never gets more than the making amount, and never pays less than the signed price
+#[test]
+fn test_partial_fills_never_exceed_order() {
+    let s = setup();
+    s.maker_asset.mint(&s.maker, &(u64::MAX as i128));
+    s.taker_asset.mint(&s.taker, &(u64::MAX as i128));
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for salt in 0..25 {
+        let mut order = build_order(&s);
+        order.salt = salt;
+        order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+        order.making_amount = 1 + rng.below(1_000_000);
+        order.taking_amount = 1 + rng.below(1_000_000);
+        let signature = sign_order(&s, &s.maker_key, &order);
+
+        let (mut made, mut taken) = (0u128, 0u128);
+        while made < order.making_amount {
+            let mut traits = taker_traits();
+            traits.maker_amount = rng.next() & 1 == 0;
+            let bound = if traits.maker_amount { order.making_amount } else { order.taking_amount };
+            let amount = 1 + rng.below(bound / 4 + 1);
+            // Budget of a single transaction for every fill
+            s.env.budget().reset_default();
+            match s.client.try_fill_order(&order, &signature, &s.taker, &amount, &traits) {
+                Ok(Ok((making_amount, taking_amount, _))) => {
+                    made += making_amount;
+                    taken += taking_amount;
+                }
+                // Amounts too small to buy anything at this price
+                Err(Ok(Error::SwapWithZeroAmount)) => {}
+                other => panic!("unexpected fill result {other:?}"),
+            }
+            assert!(made <= order.making_amount);
+            assert!(taken * order.making_amount >= made * order.taking_amount);
+        }
+        assert_eq!(s.client.order_status(&order), (OrderStatus::FullyFilled, 0));
+    }
+}
+
+/// What the listener recorded for `hook`: (call index, taker's maker-asset balance,
+/// making amount, taking amount, remaining, extra data)
+fn recorded(s: &Setup, listener: &Address, hook: Symbol) -> (u32, i128, u128, u128, u128, Bytes) {
+    s.env.as_contract(listener, || s.env.storage().instance().get(&hook).unwrap())
+}
+
+#[test]
+fn test_maker_interactions() {
+    let s = setup();
+    let listener = s.env.register_contract(None, TestListener);
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG
+        | MakerTraits::PRE_INTERACTION_CALL_FLAG
+        | MakerTraits::POST_INTERACTION_CALL_FLAG;
+    with_extension(&mut order, &[
+        (DynamicField::PreInteractionData, args_with_target(&s.env, &listener, &[7])),
+        (DynamicField::PostInteractionData, args_with_target(&s.env, &listener, &[8])),
+    ]);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    // Pre-interaction before the taker got anything, post-interaction after
+    let extra = |byte| Bytes::from_array(&s.env, &[byte]);
+    assert_eq!(recorded(&s, &listener, symbol_short!("pre")), (0, 0, 200, 100, 1000, extra(7)));
+    assert_eq!(recorded(&s, &listener, symbol_short!("post")), (1, 200, 200, 100, 1000, extra(8)));
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(recorded(&s, &listener, symbol_short!("post")), (3, 400, 200, 100, 800, extra(8)));
+}
+
+#[test]
+fn test_taker_interaction_sources_liquidity() {
+    let s = setup();
+    let listener = s.env.register_contract(None, TestListener);
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    // The taker holds none of the taker asset until its interaction runs
+    let taker = Address::generate(&s.env);
+    let args = args_with_target(&s.env, &listener, &[9]);
+    let mut traits = taker_traits();
+    traits.args_interaction_length = args.len();
+    s.client.fill_order_args(&order, &signature, &taker, &100, &traits, &args);
+
+    assert_eq!(recorded(&s, &listener, symbol_short!("taker")), (0, 200, 200, 100, 1000, Bytes::from_array(&s.env, &[9])));
+    assert_eq!(s.maker_asset.balance(&taker), 200);
+    assert_eq!(s.taker_asset.balance(&taker), 0);
+    assert_eq!(s.taker_asset.balance(&s.maker), 100);
+}
+
+#[test]
+fn test_interaction_bad_listener_data() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::PRE_INTERACTION_CALL_FLAG;
+    with_extension(&mut order, &[(DynamicField::PreInteractionData, Bytes::from_array(&s.env, &[0, 0, 0, 9, 1]))]);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::InvalidExtension)));
+
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let mut traits = taker_traits();
+    traits.args_interaction_length = 3;
+    let args = Bytes::from_array(&s.env, &[0, 0, 0]);
+    let result = s.client.try_fill_order_args(&order, &signature, &s.taker, &100, &traits, &args);
+    assert_eq!(result, Err(Ok(Error::InvalidArgs)));
+
+    // Interaction longer than the args
+    traits.args_interaction_length = 4;
+    let result = s.client.try_fill_order_args(&order, &signature, &s.taker, &100, &traits, &args);
+    assert_eq!(result, Err(Ok(Error::InvalidArgs)));
+}
+
+/// Maker-asset and taker-asset balances of each party
+fn balances(s: &Setup, parties: &[&Address]) -> std::vec::Vec<(i128, i128)> {
+    parties.iter().map(|party| (s.maker_asset.balance(party), s.taker_asset.balance(party))).collect()
+}
+
+/// Balance changes of each party between two `balances` snapshots
+fn deltas(before: &[(i128, i128)], after: &[(i128, i128)]) -> std::vec::Vec<(i128, i128)> {
+    before.iter().zip(after).map(|(b, a)| (a.0 - b.0, a.1 - b.1)).collect()
+}
+
+#[test]
+fn test_settlement_pays_receiver_directly() {
+    let s = setup();
+    let receiver = Address::generate(&s.env);
+    let mut order = build_order(&s);
+    order.receiver = receiver.clone();
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let parties = [&s.maker, &s.taker, &receiver, &s.contract_id];
+    let before = balances(&s, &parties);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    // The LOP never holds either asset
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(-200, 0), (200, -100), (0, 100), (0, 0)]);
+}
+
+#[test]
+fn test_settlement_without_receiver_pays_maker() {
+    let s = setup();
+    let mut order = build_order(&s);
+    order.receiver = s.client.any_sender();
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let parties = [&s.maker, &s.taker, &s.contract_id];
+    let before = balances(&s, &parties);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(-200, 100), (200, -100), (0, 0)]);
+}
+
+#[test]
+fn test_fill_args_settles_directly() {
+    let s = setup();
+    let receiver = Address::generate(&s.env);
+    let mut order = resolver_order(&s);
+    order.receiver = receiver.clone();
+    let signature = sign_resolver_order(&s, &order);
+    let escrow = Address::generate(&s.env);
+
+    let parties = [&s.maker, &s.taker, &escrow, &receiver, &s.contract_id];
+    let before = balances(&s, &parties);
+    let args = args_with_target(&s.env, &escrow, &[]);
+    s.client.fill_args(&order, &signature, &s.taker, &100, &args_has_target(&s.env), &args);
+
+    // Maker to escrow, resolver to receiver
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(-200, 0), (0, -100), (200, 0), (0, 100), (0, 0)]);
+}
+
+/// Multiple-fill order, so orders of one maker don't share a nonce
+fn batch_order(s: &Setup, salt: u64) -> Order {
+    let mut order = build_order(s);
+    order.salt = salt;
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    order
+}
+
+fn fill_request(s: &Setup, order: &Order, amount: u128) -> FillRequest {
+    FillRequest {
+        order: order.clone(),
+        signature: sign_order(s, &s.maker_key, order),
+        amount,
+        taker_traits: taker_traits(),
+        args: Bytes::new(&s.env),
+    }
+}
+
+#[test]
+fn test_fill_orders_aggregates_transfers() {
+    let s = setup();
+    let (first, second) = (batch_order(&s, 1), batch_order(&s, 2));
+    let requests = vec![&s.env, fill_request(&s, &first, 100), fill_request(&s, &second, 250)];
+
+    let parties = [&s.maker, &s.taker, &s.contract_id];
+    let before = balances(&s, &parties);
+    let results = s.client.fill_orders(&s.taker, &requests, &BatchMode::AllOrNothing);
+
+    assert_eq!(results, vec![
+        &s.env,
+        FillResult { order_hash: s.client.hash_order(&first), making_amount: 200, taking_amount: 100, error: 0 },
+        FillResult { order_hash: s.client.hash_order(&second), making_amount: 500, taking_amount: 250, error: 0 },
+    ]);
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(-700, 350), (700, -350), (0, 0)]);
+    // One transfer per leg for the whole batch
+    assert_eq!((s.maker_asset.transfer_count(), s.taker_asset.transfer_count()), (1, 1));
+    assert_eq!(s.client.order_status(&second), (OrderStatus::PartiallyFilled, 500));
+}
+
+#[test]
+fn test_fill_orders_best_effort_skips_failures() {
+    let s = setup();
+    let (cancelled, open) = (batch_order(&s, 1), batch_order(&s, 2));
+    s.client.cancel_order(&cancelled);
+    let requests = vec![&s.env, fill_request(&s, &cancelled, 100), fill_request(&s, &open, 100)];
+
+    let parties = [&s.maker, &s.taker];
+    let before = balances(&s, &parties);
+    let results = s.client.fill_orders(&s.taker, &requests, &BatchMode::BestEffort);
+
+    assert_eq!(results, vec![
+        &s.env,
+        FillResult { order_hash: s.client.hash_order(&cancelled), making_amount: 0, taking_amount: 0, error: Error::InvalidatedOrder as u32 },
+        FillResult { order_hash: s.client.hash_order(&open), making_amount: 200, taking_amount: 100, error: 0 },
+    ]);
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(-200, 100), (200, -100)]);
+}
+
+#[test]
+fn test_fill_orders_all_or_nothing_reverts() {
+    let s = setup();
+    let (open, cancelled) = (batch_order(&s, 1), batch_order(&s, 2));
+    s.client.cancel_order(&cancelled);
+    let requests = vec![&s.env, fill_request(&s, &open, 100), fill_request(&s, &cancelled, 100)];
+
+    let parties = [&s.maker, &s.taker];
+    let before = balances(&s, &parties);
+    let result = s.client.try_fill_orders(&s.taker, &requests, &BatchMode::AllOrNothing);
+
+    // The first order's fill is rolled back with the batch
+    assert_eq!(result, Err(Ok(Error::InvalidatedOrder)));
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(0, 0), (0, 0)]);
+    assert_eq!(s.client.order_status(&open), (OrderStatus::Unfilled, 1000));
+}
+
+#[test]
+fn test_fill_orders_same_order_twice() {
+    let s = setup();
+    let order = batch_order(&s, 1);
+    let requests = vec![&s.env, fill_request(&s, &order, 300), fill_request(&s, &order, 300)];
+
+    // The second request sees the first one's fill and takes what is left
+    let results = s.client.fill_orders(&s.taker, &requests, &BatchMode::AllOrNothing);
+    assert_eq!(results.iter().map(|r| (r.making_amount, r.taking_amount)).collect::<std::vec::Vec<_>>(), [(600, 300), (400, 200)]);
+    assert_eq!(s.client.order_status(&order), (OrderStatus::FullyFilled, 0));
+}
+
+fn listed(page: &OrderPage) -> std::vec::Vec<(BytesN<32>, u128)> {
+    page.orders.iter().map(|active| (active.order_hash, active.remaining)).collect()
+}
+
+#[test]
+fn test_post_order_indexes_by_pair_and_maker() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.sequence_number = 100);
+    let (first, second) = (batch_order(&s, 1), batch_order(&s, 2));
+    let mut reversed = batch_order(&s, 3);
+    reversed.maker_asset = s.taker_asset.address.clone();
+    reversed.taker_asset = s.maker_asset.address.clone();
+    let hashes: std::vec::Vec<_> = [&first, &second, &reversed]
+        .into_iter()
+        .map(|order| s.client.post_order(order, &Bytes::new(&s.env), &sign_order(&s, &s.maker_key, order), &1_000))
+        .collect();
+
+    let posted = s.client.posted_order(&hashes[0]).unwrap();
+    assert_eq!((posted.order, posted.expiration_ledger), (first.clone(), 1_000));
+    let pair = s.client.orders_by_pair(&s.maker_asset.address, &s.taker_asset.address, &0, &10);
+    assert_eq!((listed(&pair), pair.next), (std::vec![(hashes[0].clone(), 1000), (hashes[1].clone(), 1000)], 2));
+    assert_eq!(listed(&s.client.orders_by_maker(&s.maker, &0, &10)).len(), 3);
+
+    // Takers fill from the listing; filled and cancelled orders drop out
+    let active = pair.orders.get(0).unwrap();
+    s.client.fill_order(&active.order, &active.signature, &s.taker, &100, &taker_traits());
+    s.client.cancel_order(&second);
+    let pair = s.client.orders_by_pair(&s.maker_asset.address, &s.taker_asset.address, &0, &10);
+    assert_eq!(listed(&pair), std::vec![(hashes[0].clone(), 800)]);
+}
+
+#[test]
+fn test_post_order_pagination() {
+    let s = setup();
+    for salt in 0..5 {
+        let order = batch_order(&s, salt);
+        s.client.post_order(&order, &Bytes::new(&s.env), &sign_order(&s, &s.maker_key, &order), &1_000);
+    }
+
+    let mut cursor = 0;
+    let mut pages = std::vec::Vec::new();
+    while cursor < 5 {
+        let page = s.client.orders_by_maker(&s.maker, &cursor, &2);
+        pages.push(page.orders.len());
+        cursor = page.next;
+    }
+    assert_eq!(pages, [2, 2, 1]);
+    assert_eq!(s.client.orders_by_maker(&s.maker, &5, &2).orders.len(), 0);
+    // Limits are capped
+    assert_eq!(s.client.orders_by_maker(&s.maker, &0, &u32::MAX).next, 5);
+}
+
+#[test]
+fn test_post_order_expires_with_ledger() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.sequence_number = 100);
+    let order = batch_order(&s, 1);
+    let order_hash = s.client.post_order(&order, &Bytes::new(&s.env), &sign_order(&s, &s.maker_key, &order), &200);
+
+    s.env.ledger().with_mut(|li| li.sequence_number = 200);
+    assert_eq!(s.client.orders_by_maker(&s.maker, &0, &10).orders.len(), 1);
+    s.env.ledger().with_mut(|li| li.sequence_number = 201);
+    assert_eq!(s.client.orders_by_maker(&s.maker, &0, &10).orders.len(), 0);
+    assert_eq!(s.client.posted_order(&order_hash), None);
+}
+
+#[test]
+fn test_post_order_with_soroban_auth() {
+    let s = setup();
+    let wallet = Address::generate(&s.env);
+    fund_maker(&s, &wallet, 1000);
+    let mut order = batch_order(&s, 1);
+    order.maker = wallet.clone();
+
+    let order_hash = s.client.post_order(&order, &Bytes::new(&s.env), &Bytes::new(&s.env), &1_000);
+    assert_eq!(s.env.auths()[0].0, wallet);
+    assert!(s.client.is_order_registered(&order_hash));
+
+    let active = s.client.orders_by_pair(&s.maker_asset.address, &s.taker_asset.address, &0, &10).orders.get(0).unwrap();
+    let (making_amount, _, _) = s.client.fill_order(&active.order, &active.signature, &s.taker, &100, &taker_traits());
+    assert_eq!(making_amount, 200);
+}
+
+#[test]
+fn test_post_order_rejections() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.sequence_number = 100);
+    let order = batch_order(&s, 1);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    assert_eq!(s.client.try_post_order(&order, &Bytes::new(&s.env), &signature, &99), Err(Ok(Error::InvalidExpiration)));
+    assert_eq!(s.client.try_post_order(&order, &Bytes::new(&s.env), &signature, &u32::MAX), Err(Ok(Error::InvalidExpiration)));
+    s.client.post_order(&order, &Bytes::new(&s.env), &signature, &1_000);
+    assert_eq!(s.client.try_post_order(&order, &Bytes::new(&s.env), &signature, &2_000), Err(Ok(Error::OrderAlreadyPosted)));
+    assert_eq!(s.client.try_post_order(&order, &Bytes::new(&s.env), &Bytes::from_array(&s.env, &[0u8; 10]), &1_000), Err(Ok(Error::BadSignature)));
+
+    let cancelled = batch_order(&s, 2);
+    s.client.cancel_order(&cancelled);
+    let signature = sign_order(&s, &s.maker_key, &cancelled);
+    assert_eq!(s.client.try_post_order(&cancelled, &Bytes::new(&s.env), &signature, &1_000), Err(Ok(Error::InvalidatedOrder)));
+}
+
+#[test]
+fn test_quote_fill_matches_fill() {
+    let s = setup();
+    let order = batch_order(&s, 1);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let parties = [&s.maker, &s.taker];
+    let before = balances(&s, &parties);
+    let quote = s.client.quote_fill(&order, &signature, &s.taker, &150, &taker_traits(), &Bytes::new(&s.env));
+    assert_eq!(quote, FillQuote { order_hash: s.client.hash_order(&order), making_amount: 300, taking_amount: 150, remaining: 700, integrator_fee: 0, protocol_fee: 0, payouts: vec![&s.env, (s.maker.clone(), 150)] });
+    // A quote neither moves funds, records the fill nor asks for authorization
+    assert!(s.env.auths().is_empty());
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(0, 0), (0, 0)]);
+    assert_eq!(s.client.order_status(&order), (OrderStatus::Unfilled, 1000));
+
+    let filled = s.client.fill_order(&order, &signature, &s.taker, &150, &taker_traits());
+    assert_eq!(filled, (quote.making_amount, quote.taking_amount, quote.order_hash));
+    assert_eq!(s.client.order_status(&order), (OrderStatus::PartiallyFilled, quote.remaining));
+}
+
+#[test]
+fn test_quote_fill_with_target_matches_fill() {
+    let s = setup();
+    let order = batch_order(&s, 1);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let target = Address::generate(&s.env);
+    let traits = TakerTraits { args_has_target: true, ..taker_traits() };
+    let args = args_with_target(&s.env, &target, &[]);
+
+    // Quoted with the exact traits and args of the fill
+    let quote = s.client.quote_fill(&order, &signature, &s.taker, &150, &traits, &args);
+    let filled = s.client.fill_order_args(&order, &signature, &s.taker, &150, &traits, &args);
+    assert_eq!(filled, (quote.making_amount, quote.taking_amount, quote.order_hash));
+    assert_eq!(s.maker_asset.balance(&target), quote.making_amount as i128);
+}
+
+#[test]
+fn test_quote_fill_reports_errors() {
+    let s = setup();
+    s.env.ledger().with_mut(|li| li.timestamp = 10_000);
+    let quote = |order: &Order, amount: u128, traits: &TakerTraits| {
+        s.client.try_quote_fill(order, &sign_order(&s, &s.maker_key, order), &s.taker, &amount, traits, &Bytes::new(&s.env)).map(|_| ())
+    };
+
+    let mut private = batch_order(&s, 1);
+    private.allowed_sender = Address::generate(&s.env);
+    assert_eq!(quote(&private, 100, &taker_traits()), Err(Ok(Error::PrivateOrder)));
+
+    let mut expired = batch_order(&s, 2);
+    expired.maker_traits |= 9_000u128 << MakerTraits::EXPIRATION_OFFSET;
+    assert_eq!(quote(&expired, 100, &taker_traits()), Err(Ok(Error::OrderExpired)));
+
+    let mut predicated = batch_order(&s, 3);
+    with_predicate(&mut predicated, &timestamp_below(&s.env, 10_000));
+    assert_eq!(quote(&predicated, 100, &taker_traits()), Err(Ok(Error::PredicateIsNotTrue)));
+
+    let cancelled = batch_order(&s, 4);
+    s.client.cancel_order(&cancelled);
+    assert_eq!(quote(&cancelled, 100, &taker_traits()), Err(Ok(Error::InvalidatedOrder)));
+
+    let mut threshold = taker_traits();
+    threshold.threshold = 300;
+    assert_eq!(quote(&batch_order(&s, 5), 100, &threshold), Err(Ok(Error::MakingAmountTooLow)));
+    assert_eq!(quote(&batch_order(&s, 5), 0, &taker_traits()), Err(Ok(Error::SwapWithZeroAmount)));
+
+    let mut target = taker_traits();
+    target.args_has_target = true;
+    assert_eq!(quote(&batch_order(&s, 6), 100, &target), Err(Ok(Error::InvalidArgs)));
+}
+
+#[test]
+fn test_quote_fill_soroban_auth_order() {
+    let s = setup();
+    let wallet = Address::generate(&s.env);
+    let mut order = batch_order(&s, 1);
+    order.maker = wallet;
+
+    // Quoted without the maker's authorization
+    let quote = s.client.quote_fill(&order, &Bytes::new(&s.env), &s.taker, &100, &taker_traits(), &Bytes::new(&s.env));
+    assert_eq!((quote.making_amount, quote.taking_amount), (200, 100));
+    assert!(s.env.auths().is_empty());
+}
+
+/// Order in group `[group; 32]`, its group header as the extension's custom data
+fn grouped_order(s: &Setup, salt: u64, group: u8, mode: u8) -> Order {
+    let mut order = batch_order(s, salt);
+    order.maker_traits |= MakerTraits::ORDER_GROUP_FLAG;
+    order.interactions = Bytes::from_array(&s.env, &[group; 32]);
+    order.interactions.push_back(mode);
+    order
+}
+
+/// Data of the `GroupConsumed` events emitted so far
+fn groups_consumed(s: &Setup) -> std::vec::Vec<(Address, BytesN<32>, BytesN<32>)> {
+    let topics = vec![&s.env, String::from_str(&s.env, "GroupConsumed").into_val(&s.env)];
+    s.env.events().all().iter().filter(|(_, event_topics, _)| *event_topics == topics).map(|(_, _, data)| data.into_val(&s.env)).collect()
+}
+
+#[test]
+fn test_order_group_consumed_on_first_fill() {
+    let s = setup();
+    let (first, sibling) = (grouped_order(&s, 1, 9, 0), grouped_order(&s, 2, 9, 0));
+    let group_id = BytesN::from_array(&s.env, &[9; 32]);
+    s.client.post_order(&sibling, &Bytes::new(&s.env), &sign_order(&s, &s.maker_key, &sibling), &1_000);
+
+    let (_, _, order_hash) = s.client.fill_order(&first, &sign_order(&s, &s.maker_key, &first), &s.taker, &100, &taker_traits());
+    assert_eq!(groups_consumed(&s), [(s.maker.clone(), group_id.clone(), order_hash.clone())]);
+    assert_eq!(s.client.group_consumer(&s.maker, &group_id), Some(order_hash));
+
+    // Siblings are rejected and delisted; the consuming order fills on
+    let signature = sign_order(&s, &s.maker_key, &sibling);
+    assert_eq!(s.client.try_fill_order(&sibling, &signature, &s.taker, &100, &taker_traits()), Err(Ok(Error::GroupConsumed)));
+    assert_eq!(s.client.orders_by_maker(&s.maker, &0, &10).orders.len(), 0);
+    s.client.fill_order(&first, &sign_order(&s, &s.maker_key, &first), &s.taker, &100, &taker_traits());
+    assert_eq!(s.client.order_status(&first), (OrderStatus::PartiallyFilled, 600));
+}
+
+#[test]
+fn test_order_group_consumed_on_full_fill() {
+    let s = setup();
+    let (first, sibling) = (grouped_order(&s, 1, 9, 1), grouped_order(&s, 2, 9, 1));
+    let (first_signature, sibling_signature) = (sign_order(&s, &s.maker_key, &first), sign_order(&s, &s.maker_key, &sibling));
+
+    // Partial fills leave the group open to every member
+    s.client.fill_order(&first, &first_signature, &s.taker, &100, &taker_traits());
+    s.client.fill_order(&sibling, &sibling_signature, &s.taker, &100, &taker_traits());
+    assert_eq!(s.client.group_consumer(&s.maker, &BytesN::from_array(&s.env, &[9; 32])), None);
+
+    let (_, _, order_hash) = s.client.fill_order(&first, &first_signature, &s.taker, &400, &taker_traits());
+    assert_eq!(s.client.group_consumer(&s.maker, &BytesN::from_array(&s.env, &[9; 32])), Some(order_hash));
+    assert_eq!(s.client.try_fill_order(&sibling, &sibling_signature, &s.taker, &100, &taker_traits()), Err(Ok(Error::GroupConsumed)));
+}
+
+#[test]
+fn test_order_group_scoped_to_maker() {
+    let s = setup();
+    let group_id = BytesN::from_array(&s.env, &[9; 32]);
+    let other_key = SigningKey::from_bytes(&[11u8; 32]);
+    let other_maker = account_address(&s.env, &other_key);
+    fund_maker(&s, &other_maker, 1_000);
+    let mut other = grouped_order(&s, 1, 9, 0);
+    other.maker = other_maker.clone();
+    other.receiver = other_maker.clone();
+
+    // Another maker reusing the group id consumes its own group only
+    let (_, _, other_hash) = s.client.fill_order(&other, &sign_order(&s, &other_key, &other), &s.taker, &100, &taker_traits());
+    assert_eq!(s.client.group_consumer(&other_maker, &group_id), Some(other_hash));
+    assert_eq!(s.client.group_consumer(&s.maker, &group_id), None);
+
+    let order = grouped_order(&s, 2, 9, 0);
+    let (_, _, order_hash) = s.client.fill_order(&order, &sign_order(&s, &s.maker_key, &order), &s.taker, &100, &taker_traits());
+    assert_eq!(s.client.group_consumer(&s.maker, &group_id), Some(order_hash));
+}
+
+#[test]
+fn test_order_group_malformed() {
+    let s = setup();
+    let mut short = grouped_order(&s, 1, 9, 0);
+    short.interactions = short.interactions.slice(..32);
+    let bad_mode = grouped_order(&s, 2, 9, 2);
+    for order in [short, bad_mode] {
+        let signature = sign_order(&s, &s.maker_key, &order);
+        assert_eq!(s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits()), Err(Ok(Error::InvalidExtension)));
+    }
+}
+
+fn twap_details(env: &Env, start: u64, window: u32, slice: u128) -> Bytes {
+    let mut details = Bytes::from_array(env, &start.to_be_bytes());
+    details.extend_from_array(&window.to_be_bytes());
+    details.extend_from_array(&slice.to_be_bytes());
+    details
+}
+
+#[test]
+fn test_twap_releases_slices_over_time() {
+    let s = setup();
+    let mut order = batch_order(&s, 1);
+    with_extension(&mut order, &[(DynamicField::TwapData, twap_details(&s.env, 10_000, 3600, 250))]);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let fill = |timestamp: u64, amount: u128| {
+        s.env.ledger().with_mut(|li| li.timestamp = timestamp);
+        s.client.try_fill_order(&order, &signature, &s.taker, &amount, &taker_traits()).map(|filled| filled.map(|(making, taking, _)| (making, taking)))
+    };
+
+    assert_eq!(fill(9_999, 100), Err(Ok(Error::TimeSliceExhausted)));
+    // Fills past the released slice are reduced to it
+    assert_eq!(fill(10_000, 500), Ok(Ok((250, 125))));
+    assert_eq!(fill(13_599, 100), Err(Ok(Error::TimeSliceExhausted)));
+    // Unfilled slices carry over
+    assert_eq!(fill(10_000 + 2 * 3600, 500), Ok(Ok((500, 250))));
+    assert_eq!(fill(1_000_000, 500), Ok(Ok((250, 125))));
+    assert_eq!(s.client.order_status(&order), (OrderStatus::FullyFilled, 0));
+}
+
+#[test]
+fn test_twap_malformed() {
+    let s = setup();
+    for details in [twap_details(&s.env, 0, 0, 250), twap_details(&s.env, 0, 60, 0), Bytes::from_array(&s.env, &[1; 27])] {
+        let mut order = batch_order(&s, 1);
+        with_extension(&mut order, &[(DynamicField::TwapData, details)]);
+        let signature = sign_order(&s, &s.maker_key, &order);
+        assert_eq!(s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits()), Err(Ok(Error::InvalidExtension)));
+    }
+}
+
+struct FeeSetup {
+    integrator: Address,
+    protocol: Address,
+    whitelisted: Address,
+}
+
+/// Order charging 1% to the integrator and 0.5% to the protocol, halved for whitelisted resolvers
+fn fee_order(s: &Setup) -> (Order, FeeSetup) {
+    let fees = FeeSetup { integrator: Address::generate(&s.env), protocol: Address::generate(&s.env), whitelisted: Address::generate(&s.env) };
+    let mut details = Bytes::from_array(&s.env, &[0, 100, 0, 50, 0x13, 0x88, 1]);
+    let payload = StellarLimitOrderProtocol::address_to_bytes(&s.env, &fees.whitelisted);
+    details.append(&payload.slice(22..));
+    details.append(&args_with_target(&s.env, &fees.integrator, &[]));
+    details.append(&args_with_target(&s.env, &fees.protocol, &[]));
+    let mut order = batch_order(s, 1);
+    with_extension(&mut order, &[(DynamicField::FeeData, details)]);
+    (order, fees)
+}
+
+fn making_mode() -> TakerTraits {
+    TakerTraits { maker_amount: true, threshold: u128::MAX, ..taker_traits() }
+}
+
+#[test]
+fn test_fees_split_exactly() {
+    let s = setup();
+    let (order, fees) = fee_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    let parties = [&s.maker, &s.taker, &fees.integrator, &fees.protocol];
+    let before = balances(&s, &parties);
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &1000, &making_mode());
+
+    // 500 * 1.015 = 507.5 rounds up; fees are 508 * 100 / 10150 and 508 * 50 / 10150 rounded down
+    assert_eq!((making_amount, taking_amount), (1000, 508));
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(-1000, 501), (1000, -508), (0, 5), (0, 2)]);
+    let (_, event) = order_events::<OrderFilled>(&s, "OrderFilled").pop().unwrap();
+    assert_eq!((event.integrator_fee, event.protocol_fee), (5, 2));
+    assert_eq!(event.taking_amount, 508);
+}
+
+#[test]
+fn test_fees_taking_mode_matches_making_mode() {
+    let s = setup();
+    let (order, _) = fee_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    assert_eq!(s.client.quote_at(&order, &Bytes::new(&s.env), &1000, &0), 508);
+    let (making_amount, taking_amount, _) = s.client.fill_order(&order, &signature, &s.taker, &508, &taker_traits());
+    assert_eq!((making_amount, taking_amount), (1000, 508));
+}
+
+#[test]
+fn test_fees_whitelist_discount() {
+    let s = setup();
+    let (order, fees) = fee_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.taker_asset.mint(&fees.whitelisted, &1000);
+
+    let quote = s.client.quote_fill(&order, &signature, &fees.whitelisted, &1000, &making_mode(), &Bytes::new(&s.env));
+    // The protocol fee drops to 25 bps: 500 * 1.0125 = 506.25 rounds up
+    assert_eq!((quote.taking_amount, quote.integrator_fee, quote.protocol_fee), (507, 5, 1));
+    assert_eq!(quote.payouts, vec![&s.env, (s.maker.clone(), 501), (fees.integrator.clone(), 5), (fees.protocol.clone(), 1)]);
+    let parties = [&s.maker, &fees.whitelisted, &fees.integrator, &fees.protocol];
+    let before = balances(&s, &parties);
+    s.client.fill_order(&order, &signature, &fees.whitelisted, &1000, &making_mode());
+    assert_eq!(deltas(&before, &balances(&s, &parties)), [(-1000, 501), (1000, -507), (0, 5), (0, 1)]);
+}
+
+#[test]
+fn test_fees_batch_pays_recipients_once() {
+    let s = setup();
+    let (first, fees) = fee_order(&s);
+    let mut second = first.clone();
+    second.salt = 2;
+    let requests = vec![&s.env, fill_request(&s, &first, 508), fill_request(&s, &second, 508)];
+    s.client.fill_orders(&s.taker, &requests, &BatchMode::AllOrNothing);
+
+    assert_eq!(s.taker_asset.transfer_count(), 3);
+    assert_eq!((s.taker_asset.balance(&fees.integrator), s.taker_asset.balance(&fees.protocol)), (10, 4));
+}
+
+#[test]
+fn test_fees_malformed() {
+    let s = setup();
+    let (order, _) = fee_order(&s);
+    let details = extension::field(&order, DynamicField::FeeData).unwrap();
+    let mut too_high = details.clone();
+    too_high.set(0, 0x27);
+    too_high.set(1, 0x11);
+    let mut trailing = details.clone();
+    trailing.push_back(0);
+    for details in [too_high, trailing, details.slice(..6)] {
+        let mut order = batch_order(&s, 1);
+        with_extension(&mut order, &[(DynamicField::FeeData, details)]);
+        let signature = sign_order(&s, &s.maker_key, &order);
+        assert_eq!(s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits()), Err(Ok(Error::InvalidExtension)));
+    }
+}
+
+/// Topics (maker, order hash) and data of the `name` events emitted by the last call
+fn order_events<T: TryFromVal<Env, Val>>(s: &Setup, name: &str) -> std::vec::Vec<((Address, BytesN<32>), T)> {
+    let symbol = Symbol::new(&s.env, name);
+    s.env
+        .events()
+        .all()
+        .iter()
+        .filter(|(contract, topics, _)| *contract == s.contract_id && topics.len() == 3 && Symbol::try_from_val(&s.env, &topics.get(0).unwrap()).ok() == Some(symbol.clone()))
+        .map(|(_, topics, data)| ((topics.get(1).unwrap().into_val(&s.env), topics.get(2).unwrap().into_val(&s.env)), T::try_from_val(&s.env, &data).unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_order_filled_event() {
+    let s = setup();
+    let order = batch_order(&s, 1);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let (_, _, order_hash) = s.client.fill_order(&order, &signature, &s.taker, &300, &making_mode());
+
+    assert_eq!(
+        order_events::<OrderFilled>(&s, "OrderFilled"),
+        [(
+            (s.maker.clone(), order_hash),
+            OrderFilled {
+                taker: s.taker.clone(),
+                maker_asset: s.maker_asset.address.clone(),
+                taker_asset: s.taker_asset.address.clone(),
+                making_amount: 300,
+                taking_amount: 150,
+                remaining: 700,
+                mode: FillMode::Making,
+                allowed_sender: s.client.any_sender(),
+                integrator_fee: 0,
+                protocol_fee: 0,
+            },
+        )]
+    );
+}
+
+#[test]
+fn test_order_cancelled_event() {
+    let s = setup();
+    let order = batch_order(&s, 1);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    s.client.cancel_order(&order);
+
+    let event = OrderCancelled {
+        maker_asset: s.maker_asset.address.clone(),
+        taker_asset: s.taker_asset.address.clone(),
+        making_amount: 1000,
+        taking_amount: 500,
+        remaining: 800,
+    };
+    assert_eq!(order_events::<OrderCancelled>(&s, "OrderCancelled"), [((s.maker.clone(), s.client.hash_order(&order)), event)]);
+}
+
+const XLM: i64 = 10_000_000;
+
+/// Env whose ledger holds a Stellar account for each key, funded with the given XLM balance
+/// in stroops. Accounts have to exist before the env is built: the native SAC has no admin
+/// to mint with, it moves the balances of account entries.
+fn env_with_accounts(accounts: &[(&SigningKey, i64)]) -> Env {
+    let mut snapshot = Env::default().to_snapshot();
+    for (key, balance) in accounts {
+        let account_id = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(*key.verifying_key().as_bytes())));
+        let entry = LedgerEntry {
+            data: LedgerEntryData::Account(AccountEntry {
+                account_id: account_id.clone(),
+                balance: *balance,
+                flags: 0,
+                home_domain: Default::default(),
+                inflation_dest: None,
+                num_sub_entries: 0,
+                seq_num: SequenceNumber(0),
+                thresholds: Thresholds([1; 4]),
+                signers: Default::default(),
+                ext: AccountEntryExt::V0,
+            }),
+            last_modified_ledger_seq: 0,
+            ext: LedgerEntryExt::V0,
+        };
+        let key = LedgerKey::Account(LedgerKeyAccount { account_id });
+        snapshot.ledger.ledger_entries.push((std::boxed::Box::new(key), (std::boxed::Box::new(entry), None)));
+    }
+    Env::from_snapshot(snapshot)
+}
+
+/// Setup with a native SAC and an account taker; the maker and the taker hold the given XLM
+/// balances, or have no account at all for `None`
+fn native_setup<'a>(maker_xlm: Option<i64>, taker_xlm: Option<i64>) -> (Setup<'a>, token::Client<'a>) {
+    let maker_key = SigningKey::from_bytes(&[7u8; 32]);
+    let taker_key = SigningKey::from_bytes(&[9u8; 32]);
+    let accounts: std::vec::Vec<_> = [(&maker_key, maker_xlm), (&taker_key, taker_xlm)]
+        .into_iter()
+        .filter_map(|(key, balance)| Some((key, balance?)))
+        .collect();
+    let s = setup_in(env_with_accounts(&accounts));
+    let xlm = s.env.deployer().with_stellar_asset(Bytes::from_array(&s.env, &[0; 4])).deploy();
+    let taker = account_address(&s.env, &taker_key);
+    s.taker_asset.mint(&taker, &1_000_000);
+    let xlm = token::Client::new(&s.env, &xlm);
+    if maker_xlm.is_some() {
+        xlm.approve(&s.maker, &s.contract_id, &i128::MAX, &(s.env.ledger().sequence() + 1_000));
+    }
+    (Setup { taker, ..s }, xlm)
+}
+
+#[test]
+fn test_native_asset_address() {
+    let (s, xlm) = native_setup(None, None);
+    assert_eq!(s.env.as_contract(&s.contract_id, || native::asset(&s.env)), xlm.address);
+}
+
+#[test]
+fn test_maker_unwrap_native() {
+    let (s, xlm) = native_setup(Some(10 * XLM), Some(10 * XLM));
+    let mut order = build_order(&s);
+    order.taker_asset = xlm.address.clone();
+    order.maker_traits = MakerTraits::UNWRAP_NATIVE_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(xlm.balance(&s.maker), 10 * XLM as i128 + 100);
+    assert_eq!(xlm.balance(&s.taker), 10 * XLM as i128 - 100);
+    assert_eq!(s.maker_asset.balance(&s.taker), 200);
+}
+
+#[test]
+fn test_taker_unwrap_native() {
+    let (s, xlm) = native_setup(Some(10 * XLM), Some(10 * XLM));
+    let mut order = build_order(&s);
+    order.maker_asset = xlm.address.clone();
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let traits = TakerTraits { unwrap_native: true, ..taker_traits() };
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &traits);
+    assert_eq!(xlm.balance(&s.taker), 10 * XLM as i128 + 200);
+    assert_eq!(xlm.balance(&s.maker), 10 * XLM as i128 - 200);
+    assert_eq!(s.taker_asset.balance(&s.maker), 100);
+}
+
+#[test]
+fn test_unwrap_native_requires_native_asset() {
+    let (s, _) = native_setup(Some(10 * XLM), Some(10 * XLM));
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::UNWRAP_NATIVE_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::NotNativeAsset)));
+
+    // An issued asset's SAC is not native XLM either
+    order.taker_asset = s.env.register_stellar_asset_contract(Address::generate(&s.env));
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::NotNativeAsset)));
+
+    let order = build_order(&s);
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let traits = TakerTraits { unwrap_native: true, ..taker_traits() };
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &traits);
+    assert_eq!(result, Err(Ok(Error::NotNativeAsset)));
+}
+
+#[test]
+fn test_native_payer_keeps_minimum_reserve() {
+    let min_balance = native::MIN_ACCOUNT_BALANCE as i64;
+    let (s, xlm) = native_setup(Some(10 * XLM), Some(min_balance + 150));
+    let mut order = build_order(&s);
+    order.taker_asset = xlm.address.clone();
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    // A second fill would leave the taker below the reserve
+    let result = s.client.try_quote_fill(&order, &signature, &s.taker, &100, &taker_traits(), &Bytes::new(&s.env));
+    assert_eq!(result, Err(Ok(Error::NativeReserveNotMet)));
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::NativeReserveNotMet)));
+
+    s.client.fill_order(&order, &signature, &s.taker, &50, &taker_traits());
+    assert_eq!(xlm.balance(&s.taker), min_balance as i128);
+}
+
+#[test]
+fn test_native_recipient_must_exist() {
+    // The taker has no account to receive the making amount in
+    let (s, xlm) = native_setup(Some(10 * XLM), None);
+    let mut order = build_order(&s);
+    order.maker_asset = xlm.address.clone();
+    let signature = sign_order(&s, &s.maker_key, &order);
+    let result = s.client.try_fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    assert_eq!(result, Err(Ok(Error::NativeReserveNotMet)));
+
+    // Contract targets hold XLM without an account
+    let target = Address::generate(&s.env);
+    let traits = TakerTraits { args_has_target: true, ..taker_traits() };
+    let args = args_with_target(&s.env, &target, &[]);
+    s.client.fill_order_args(&order, &signature, &s.taker, &100, &traits, &args);
+    assert_eq!(xlm.balance(&target), 200);
+}
+
+#[test]
+fn test_issued_asset_has_no_reserve() {
+    let s = setup();
+    let admin = Address::generate(&s.env);
+    let issued = s.env.register_stellar_asset_contract(admin);
+    token::StellarAssetClient::new(&s.env, &issued).mint(&s.taker, &100);
+    let mut order = build_order(&s);
+    order.taker_asset = issued.clone();
+    // Contract receivers need no trustline
+    order.receiver = Address::generate(&s.env);
+    let signature = sign_order(&s, &s.maker_key, &order);
+
+    s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+    let issued = token::Client::new(&s.env, &issued);
+    assert_eq!(issued.balance(&s.taker), 0);
+    assert_eq!(issued.balance(&order.receiver), 100);
+}
+
+/// Fill `fills` independent orders and return the cost of an early fill and of the last one:
+/// per-order entries keep the work of a fill constant.
+///
+/// The test host keeps the whole ledger in one sorted map, so every storage access also pays
+/// memory costs (MemAlloc/MemCpy/MemCmp) that grow with the number of entries in the test
+/// ledger. On chain only the transaction footprint is loaded, so those are left out.
+fn fill_costs(fills: u64) -> ((u64, u64, u64), (u64, u64, u64)) {
+    let s = setup();
+    s.maker_asset.mint(&s.maker, &(fills as i128 * 1000));
+    s.taker_asset.mint(&s.taker, &(fills as i128 * 1000));
+    let mut order = build_order(&s);
+    order.maker_traits = MakerTraits::ALLOW_MULTIPLE_FILLS_FLAG;
+
+    let mut costs = std::vec::Vec::new();
+    for i in 0..fills {
+        order.salt = i;
+        let signature = sign_order(&s, &s.maker_key, &order);
+        s.env.budget().reset_unlimited();
+        s.client.fill_order(&order, &signature, &s.taker, &100, &taker_traits());
+
+        let budget = s.env.budget();
+        let host_memory: u64 = [ContractCostType::MemAlloc, ContractCostType::MemCpy, ContractCostType::MemCmp]
+            .into_iter()
+            .map(|cost_type| budget.tracker(cost_type).cpu)
+            .sum();
+        costs.push((
+            budget.cpu_instruction_cost() - host_memory,
+            budget.tracker(ContractCostType::ValSer).inputs.unwrap_or(0),
+            budget.tracker(ContractCostType::VisitObject).iterations,
+        ));
+    }
+    (costs[10], costs[fills as usize - 1])
+}
+
+#[test]
+fn test_fill_cost_stays_flat() {
+    let (first, last) = fill_costs(200);
+    assert_eq!(first, last);
+}
+
+/// Thousands of fills, enough to tell flat cost from slow growth; run with `--ignored`
+#[test]
+#[ignore]
+fn test_fill_cost_stays_flat_over_thousands_of_fills() {
+    let (first, last) = fill_costs(5_000);
+    assert_eq!(first, last);
+}
@@ -0,0 +1,41 @@
+//! Time-sliced (TWAP) orders: the making amount is released in slices over time
+//!
+//! The TWAP data field holds, big-endian:
+//!
+//! | Bytes  | Field                                           |
+//! |--------|-------------------------------------------------|
+//! | 0..8   | start time (unix seconds)                       |
+//! | 8..12  | window length (seconds)                         |
+//! | 12..28 | making amount released per window               |
+//!
+//! The first slice is released at the start time and one more at the start of each window.
+//! Slices left unfilled carry over. What has been filled is read off the remaining amount, so
+//! no extra state is kept: a fill can take at most the released amount not yet filled.
+
+use soroban_sdk::Bytes;
+
+use crate::Error;
+
+const DETAILS_LEN: u32 = 28;
+
+/// Making amount that can be filled at `timestamp` out of `remaining`
+pub fn available(details: &Bytes, making_amount: u128, remaining: u128, timestamp: u64) -> Result<u128, Error> {
+    if details.len() != DETAILS_LEN {
+        return Err(Error::InvalidExtension);
+    }
+    let mut data = [0u8; DETAILS_LEN as usize];
+    details.copy_into_slice(&mut data);
+    let start = u64::from_be_bytes(data[0..8].try_into().unwrap());
+    let window = u32::from_be_bytes(data[8..12].try_into().unwrap()) as u64;
+    let slice = u128::from_be_bytes(data[12..28].try_into().unwrap());
+    if window == 0 || slice == 0 {
+        return Err(Error::InvalidExtension);
+    }
+    if timestamp < start {
+        return Ok(0);
+    }
+    let windows = ((timestamp - start) / window + 1) as u128;
+    let released = slice.saturating_mul(windows).min(making_amount);
+    let filled = making_amount - remaining;
+    Ok(released.saturating_sub(filled).min(remaining))
+}
@@ -1,8 +1,12 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, vec, Address, Bytes, BytesN, Env, IntoVal, Symbol, Vec, String, xdr::{ScErrorCode, ScErrorType, ToXdr}, token::TokenClient, I256,
+    auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation},
+    contract, contractimpl, contracttype, symbol_short, vec, Address, Bytes, BytesN, Env, IntoVal, Symbol, Vec, xdr::{ScErrorCode, ScErrorType, ToXdr}, token::TokenClient, I256,
 };
 
+/// XDR of `Asset::Native`, from which the native SAC address is derived
+const NATIVE_ASSET_XDR: [u8; 4] = [0; 4];
+
 // Define ResolverOrder locally to avoid import issues
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -17,6 +21,19 @@ pub struct ResolverOrder {
     pub maker_traits: u128,       // MakerTraits as uint256
 }
 
+// Amounts a LOP fill would settle (matching the LOP's FillQuote)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FillQuote {
+    pub order_hash: BytesN<32>,
+    pub making_amount: u128,
+    pub taking_amount: u128,
+    pub remaining: u128,
+    pub integrator_fee: u128,
+    pub protocol_fee: u128,
+    pub payouts: Vec<(Address, u128)>, // Transfers of the taking amount from the resolver
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Order {
@@ -142,9 +159,7 @@ impl StellarResolver {
 
         // Set _ARGS_HAS_TARGET flag (equivalent to EVM takerTraits = TakerTraits.wrap(...))
         // CRITICAL: This sets bit 251 to indicate args contains target address
-        // Since I256 doesn't support arithmetic ops, we use a workaround
-        // For now, we'll use the original taker_traits and handle this in the LOP
-        let taker_traits_with_target = taker_traits.clone();
+        let taker_traits_with_target = Self::with_args_has_target(env, &taker_traits);
 
         // Prepare args with target (equivalent to EVM abi.encodePacked(computed, args))
         let args_with_target = Self::prepare_args_with_target(env, &escrow_address, &args)?;
@@ -271,8 +286,7 @@ impl StellarResolver {
         immutables: &BaseEscrowImmutables,
     ) -> Result<(), Error> {
         // Use native XLM SAC for safety deposit transfer
-        // The native asset address is the string "native"
-        let native = Address::from_string(&String::from_str(env, "native"));
+        let native = env.deployer().with_stellar_asset(Bytes::from_array(env, &NATIVE_ASSET_XDR)).deployed_address();
         let token = TokenClient::new(env, &native);
         
         // Transfer safety deposit from resolver to escrow
@@ -282,21 +296,35 @@ impl StellarResolver {
         Ok(())
     }
 
+    /// Set the _ARGS_HAS_TARGET bit (251) of the taker traits word
+    fn with_args_has_target(env: &Env, taker_traits: &TakerTraits) -> TakerTraits {
+        let mut word = taker_traits.0.to_be_bytes();
+        // Bit 251 is bit 3 of the most significant byte
+        word.set(0, word.get(0).unwrap_or(0) | 0x08);
+        TakerTraits(I256::from_be_bytes(env, &word))
+    }
+
     /// Prepare args with target (equivalent to EVM abi.encodePacked(computed, args))
+    ///
+    /// The target is length-prefixed since Stellar addresses have no fixed width:
+    /// a 4-byte big-endian length, then the address XDR.
     fn prepare_args_with_target(
         env: &Env,
         escrow_address: &Address,
         args: &Bytes,
     ) -> Result<Bytes, Error> {
         // Combine escrow address and args (equivalent to EVM abi.encodePacked)
-        let mut combined = Bytes::new(env);
         let address_bytes = escrow_address.to_xdr(env);
+        let mut combined = Bytes::from_array(env, &address_bytes.len().to_be_bytes());
         combined.append(&address_bytes);
         combined.append(args);
         Ok(combined)
     }
 
     /// Execute order on Limit Order Protocol (equivalent to EVM _LOP.fillOrderArgs)
+    ///
+    /// The LOP pays the taking amount out of the resolver one call below this contract's, where
+    /// invoker auth doesn't reach, so the payouts quoted for the fill are authorized first.
     fn execute_order_on_lop(
         env: &Env,
         lop_contract: &Address,
@@ -322,10 +350,25 @@ impl StellarResolver {
             env,
             lop_order.into_val(env),
             signature.clone().into_val(env),
+            env.current_contract_address().into_val(env), // The resolver pays the taking amount
             (*amount).into_val(env),
             taker_traits.clone().into_val(env),
             args.clone().into_val(env),
         ];
+
+        let quote: FillQuote = env.invoke_contract(lop_contract, &Symbol::new(env, "quote_args"), args.clone());
+        let mut transfers = Vec::new(env);
+        for (recipient, amount) in quote.payouts.iter().filter(|(_, amount)| *amount > 0) {
+            transfers.push_back(InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: order.taker_asset.clone(),
+                    fn_name: Symbol::new(env, "transfer"),
+                    args: vec![env, env.current_contract_address().into_val(env), recipient.into_val(env), (amount as i128).into_val(env)],
+                },
+                sub_invocations: Vec::new(env),
+            }));
+        }
+        env.authorize_as_current_contract(transfers);
         
         let result: Result<soroban_sdk::Val, soroban_sdk::Error> = 
             env.invoke_contract(lop_contract, &symbol_short!("fill_args"), args);
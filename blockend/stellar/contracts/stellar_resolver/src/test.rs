@@ -0,0 +1,186 @@
+#![cfg(test)]
+extern crate std;
+
+use super::*;
+use soroban_sdk::{
+    contract, contractimpl,
+    testutils::{Address as _, MockAuth, MockAuthInvoke},
+    token::StellarAssetClient,
+    xdr::{
+        AccountEntry, AccountEntryExt, AccountId, FromXdr, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey, LedgerKeyAccount,
+        PublicKey, SequenceNumber, Thresholds, Uint256,
+    },
+};
+use stellar_limit_order_protocol::{StellarLimitOrderProtocol, StellarLimitOrderProtocolClient};
+
+const XLM: i128 = 10_000_000;
+const FUNDER_KEY: [u8; 32] = [9; 32];
+
+/// Factory stand-in answering `get_addr` with a preset escrow address
+#[contract]
+struct MockFactory;
+
+#[contractimpl]
+impl MockFactory {
+    pub fn set_addr(env: Env, escrow: Address) {
+        env.storage().instance().set(&symbol_short!("escrow"), &escrow);
+    }
+
+    pub fn get_addr(env: Env, _hashlock: BytesN<32>) -> Address {
+        env.storage().instance().get(&symbol_short!("escrow")).unwrap()
+    }
+}
+
+struct Setup<'a> {
+    env: Env,
+    resolver: Address,
+    client: StellarResolverClient<'a>,
+    lop: StellarLimitOrderProtocolClient<'a>,
+    admin: Address,
+    escrow: Address,
+    native: TokenClient<'a>,
+    maker_asset: TokenClient<'a>,
+    taker_asset: TokenClient<'a>,
+}
+
+/// Env whose ledger holds a Stellar account funded with XLM, since the native SAC has no admin to mint with
+fn env_with_funder(balance: i128) -> (Env, Address) {
+    let mut snapshot = Env::default().to_snapshot();
+    let account_id = AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(FUNDER_KEY)));
+    let entry = LedgerEntry {
+        data: LedgerEntryData::Account(AccountEntry {
+            account_id: account_id.clone(),
+            balance: balance as i64,
+            flags: 0,
+            home_domain: Default::default(),
+            inflation_dest: None,
+            num_sub_entries: 0,
+            seq_num: SequenceNumber(0),
+            thresholds: Thresholds([1; 4]),
+            signers: Default::default(),
+            ext: AccountEntryExt::V0,
+        }),
+        last_modified_ledger_seq: 0,
+        ext: LedgerEntryExt::V0,
+    };
+    let key = LedgerKey::Account(LedgerKeyAccount { account_id });
+    snapshot.ledger.ledger_entries.push((std::boxed::Box::new(key), (std::boxed::Box::new(entry), None)));
+    let env = Env::from_snapshot(snapshot);
+
+    let mut xdr = [0u8; 44];
+    xdr[3] = 18;
+    xdr[12..].copy_from_slice(&FUNDER_KEY);
+    let funder = Address::from_xdr(&env, &Bytes::from_array(&env, &xdr)).unwrap();
+    (env, funder)
+}
+
+fn setup<'a>() -> Setup<'a> {
+    let (env, funder) = env_with_funder(100 * XLM);
+    env.mock_all_auths();
+
+    let lop_id = env.register_contract(None, StellarLimitOrderProtocol);
+    let lop = StellarLimitOrderProtocolClient::new(&env, &lop_id);
+    lop.initialize(&Address::generate(&env));
+
+    let escrow = Address::generate(&env);
+    let factory = env.register_contract(None, MockFactory);
+    MockFactoryClient::new(&env, &factory).set_addr(&escrow);
+
+    let admin = Address::generate(&env);
+    let resolver = env.register_contract(None, StellarResolver);
+    let client = StellarResolverClient::new(&env, &resolver);
+    client.initialize(&factory, &lop_id, &admin);
+
+    // The resolver holds the XLM for safety deposits and the taker asset it pays makers in
+    let native = TokenClient::new(&env, &env.deployer().with_stellar_asset(Bytes::from_array(&env, &NATIVE_ASSET_XDR)).deploy());
+    native.transfer(&funder, &resolver, &(10 * XLM));
+    let maker_asset = TokenClient::new(&env, &env.register_stellar_asset_contract(Address::generate(&env)));
+    let taker_asset_id = env.register_stellar_asset_contract(Address::generate(&env));
+    StellarAssetClient::new(&env, &taker_asset_id).mint(&resolver, &1000);
+    let taker_asset = TokenClient::new(&env, &taker_asset_id);
+
+    Setup { env, resolver, client, lop, admin, escrow, native, maker_asset, taker_asset }
+}
+
+/// Order from a contract wallet that registers it on the LOP, so it fills without a signature
+fn registered_order(s: &Setup, receiver: &Address) -> Order {
+    let maker = Address::generate(&s.env);
+    StellarAssetClient::new(&s.env, &s.maker_asset.address).mint(&maker, &1000);
+    s.maker_asset.approve(&maker, &s.lop.address, &1000, &(s.env.ledger().sequence() + 1000));
+
+    let order = Order {
+        salt: 1,
+        maker: maker.clone(),
+        receiver: receiver.clone(),
+        maker_asset: s.maker_asset.address.clone(),
+        taker_asset: s.taker_asset.address.clone(),
+        making_amount: 1000,
+        taking_amount: 500,
+        maker_traits: 0,
+    };
+    s.lop.register_order(&stellar_limit_order_protocol::Order {
+        salt: order.salt,
+        maker_asset: order.maker_asset.clone(),
+        taker_asset: order.taker_asset.clone(),
+        maker,
+        receiver: order.receiver.clone(),
+        allowed_sender: s.lop.any_sender(),
+        making_amount: order.making_amount,
+        taking_amount: order.taking_amount,
+        maker_traits: order.maker_traits,
+        offsets: 0,
+        interactions: Bytes::new(&s.env),
+        evm_signer: BytesN::from_array(&s.env, &[0; 20]),
+    });
+    order
+}
+
+fn immutables(s: &Setup, order: &Order) -> BaseEscrowImmutables {
+    BaseEscrowImmutables {
+        order_hash: BytesN::from_array(&s.env, &[1; 32]),
+        hashlock: BytesN::from_array(&s.env, &[2; 32]),
+        maker: order.maker.clone(),
+        taker: s.resolver.clone(),
+        token: order.maker_asset.clone(),
+        amount: order.making_amount,
+        safety_deposit: XLM as u128,
+        timelocks: Timelocks {
+            finality: 60,
+            src_withdrawal: 120,
+            src_public_withdrawal: 180,
+            src_cancellation: 240,
+            src_public_cancellation: 300,
+            dst_withdrawal: 360,
+            dst_public_withdrawal: 420,
+            dst_cancellation: 480,
+            deployed_at: 0,
+        },
+    }
+}
+
+#[test]
+fn test_deploy_src_fills_order_into_escrow() {
+    let s = setup();
+    let receiver = Address::generate(&s.env);
+    let order = registered_order(&s, &receiver);
+    let immutables = immutables(&s, &order);
+    let taker_traits = TakerTraits(I256::from_i32(&s.env, 0));
+
+    // Only the admin signs: the resolver authorizes its own payment to the maker
+    s.env.mock_auths(&[MockAuth {
+        address: &s.admin,
+        invoke: &MockAuthInvoke {
+            contract: &s.resolver,
+            fn_name: "deploy_src",
+            args: (immutables.clone(), order.clone(), Bytes::new(&s.env), 500u128, taker_traits.clone(), Bytes::new(&s.env)).into_val(&s.env),
+            sub_invokes: &[],
+        },
+    }]);
+    s.client.deploy_src(&immutables, &order, &Bytes::new(&s.env), &500, &taker_traits, &Bytes::new(&s.env));
+
+    assert_eq!(s.maker_asset.balance(&s.escrow), 1000);
+    assert_eq!(s.taker_asset.balance(&receiver), 500);
+    assert_eq!(s.taker_asset.balance(&s.resolver), 500);
+    assert_eq!(s.native.balance(&s.escrow), XLM);
+    assert_eq!(s.native.balance(&s.resolver), 9 * XLM);
+}